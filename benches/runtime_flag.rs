@@ -0,0 +1,44 @@
+//! Compares the cost of a disabled evaluation for the feature-gated and the runtime-gated
+//! macros. Run with `cargo bench --bench runtime_flag`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 10_000_000;
+
+fn gated() -> Result<(), String> {
+    chaos_rs::maybe_fail!("bench_gated");
+    Ok(())
+}
+
+fn runtime() -> Result<(), String> {
+    chaos_rs::maybe_fail_runtime!("bench_runtime");
+    Ok(())
+}
+
+fn per_call(f: fn() -> Result<(), String>) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let _ = black_box(f());
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    println!(
+        "maybe_fail! (tag disabled):           {:?}/call",
+        per_call(gated)
+    );
+
+    chaos_rs::set_runtime_chaos(false);
+    println!(
+        "maybe_fail_runtime! (switch off):     {:?}/call",
+        per_call(runtime)
+    );
+
+    chaos_rs::set_runtime_chaos(true);
+    println!(
+        "maybe_fail_runtime! (tag disabled):   {:?}/call",
+        per_call(runtime)
+    );
+}
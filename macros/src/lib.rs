@@ -0,0 +1,115 @@
+//! Procedural macros for `chaos_rs`. Use them through the `macros` feature of `chaos-rs`
+//! rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{Item, ItemFn, LitStr, Stmt, parse_macro_input};
+
+/// Clears all failpoints before and after the annotated function runs.
+///
+/// The body is wrapped so that `chaos_rs::clear_all_failpoints()` runs first and again when
+/// the body finishes, including when it panics, so state a test leaks can't reach the next
+/// one. The function's return value is passed through unchanged.
+///
+/// The attribute doesn't add `#[test]` itself; place it above the test attribute so it
+/// composes with `#[test]`, `#[tokio::test]`, `#[should_panic]` and the like. Clearing is
+/// process-wide, so tests that run in parallel in the same binary still share state; keep
+/// them in their own test binary or run with `--test-threads=1` for full isolation.
+///
+/// # Example
+/// ```rust,ignore
+/// #[chaos_rs::chaos_test]
+/// #[test]
+/// fn fails_cleanly() {
+///     chaos_rs::__failpoint_internal::enable_failpoint("db_fail");
+///     // no manual teardown needed
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn chaos_test(args: TokenStream, item: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        let args = proc_macro2::TokenStream::from(args);
+        return syn::Error::new_spanned(args, "`#[chaos_test]` takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let mut function = match parse_macro_input!(item as Item) {
+        Item::Fn(function) => function,
+        other => {
+            return syn::Error::new_spanned(
+                other,
+                "`#[chaos_test]` can only be applied to a function",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    let body = &function.block;
+    function.block = syn::parse_quote!({
+        ::chaos_rs::clear_all_failpoints();
+        let _chaos_test_guard = ::chaos_rs::__failpoint_internal::ClearAllOnDrop;
+        #body
+    });
+
+    quote!(#function).into()
+}
+
+/// Injects a failure at one randomly chosen statement of the annotated function.
+///
+/// When the failpoint named by the tag fires, one of the function's top-level statements
+/// is picked at random (using the generator seeded by `chaos_rs::set_seed`) and the
+/// function returns `Err(tag.into())` just before it, so each invocation fails at exactly
+/// one place and different runs fail at different places. When the failpoint doesn't fire
+/// the function runs unchanged.
+///
+/// The transformation evaluates the failpoint once on entry, then inserts a check before
+/// each top-level statement, including a trailing expression; nested blocks and item
+/// definitions are left alone. Like `maybe_fail!`, the checks are compiled only with the
+/// `chaos` feature and require a return type of `Result<_, E>` where `E: From<&str>`.
+///
+/// # Example
+/// ```rust,ignore
+/// #[chaos_rs::chaos_scatter("save_profile")]
+/// fn save_profile(db: &mut Db) -> Result<(), String> {
+///     db.begin()?;
+///     db.write_user()?;
+///     db.write_settings()?;
+///     db.commit()
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn chaos_scatter(args: TokenStream, item: TokenStream) -> TokenStream {
+    let tag = parse_macro_input!(args as LitStr);
+    let mut function = parse_macro_input!(item as ItemFn);
+
+    let sites = function
+        .block
+        .stmts
+        .iter()
+        .filter(|stmt| !matches!(stmt, Stmt::Item(_)))
+        .count();
+    let mut stmts: Vec<Stmt> = Vec::with_capacity(function.block.stmts.len() * 2 + 1);
+    stmts.push(syn::parse_quote! {
+        #[cfg(feature = "chaos")]
+        let __chaos_scatter_site =
+            ::chaos_rs::__failpoint_internal::scatter_site(#tag, #sites);
+    });
+    let mut site = 0usize;
+    for stmt in std::mem::take(&mut function.block.stmts) {
+        if !matches!(stmt, Stmt::Item(_)) {
+            stmts.push(syn::parse_quote! {
+                #[cfg(feature = "chaos")]
+                if __chaos_scatter_site == ::core::option::Option::Some(#site) {
+                    return ::core::result::Result::Err(::core::convert::From::from(#tag));
+                }
+            });
+            site += 1;
+        }
+        stmts.push(stmt);
+    }
+    function.block.stmts = stmts;
+
+    quote!(#function).into()
+}
@@ -1,21 +1,1890 @@
+use crate::injections;
+use crate::session;
+use crate::snapshot::TagState;
+use crate::timing::{ClockSkew, SleepStats};
+use crate::{Action, Direction};
 #[doc(hidden)]
-use dashmap::DashSet;
-use std::sync::LazyLock;
+use dashmap::{DashMap, DashSet};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::panic::{AssertUnwindSafe, Location};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, LazyLock, Mutex, RwLock};
+use std::task::{Context, Poll, Waker};
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
 
 pub static FAILPOINTS: LazyLock<DashSet<&'static str>> = LazyLock::new(DashSet::new);
 
+/// The number of tags in `FAILPOINTS`, updated once per enable or disable call.
+static ENABLED_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+static DIRECTIONS: LazyLock<DashMap<&'static str, Direction>> = LazyLock::new(DashMap::new);
+
+static CHILDREN: LazyLock<DashMap<&'static str, Vec<&'static str>>> = LazyLock::new(DashMap::new);
+
+static KNOWN_TAGS: LazyLock<DashSet<&'static str>> = LazyLock::new(DashSet::new);
+
+/// Tags leaked by `intern_tag`, kept so each is leaked only once.
+static INTERNED_TAGS: LazyLock<DashSet<&'static str>> = LazyLock::new(DashSet::new);
+
+static ORIGINS: LazyLock<DashMap<&'static str, &'static Location<'static>>> =
+    LazyLock::new(DashMap::new);
+
+struct Cooldown {
+    period: Duration,
+    last_fire: Option<Instant>,
+}
+
+static COOLDOWNS: LazyLock<DashMap<&'static str, Cooldown>> = LazyLock::new(DashMap::new);
+
+struct Limit {
+    remaining_hits: u64,
+    /// `None` when `max_duration` is too large to represent, i.e. no time limit.
+    deadline: Option<Instant>,
+}
+
+static LIMITS: LazyLock<DashMap<&'static str, Limit>> = LazyLock::new(DashMap::new);
+
+static WRITE_FRACTIONS: LazyLock<DashMap<&'static str, f64>> = LazyLock::new(DashMap::new);
+
+static SLEEP_OVERRIDES: LazyLock<DashMap<&'static str, Duration>> = LazyLock::new(DashMap::new);
+
+static RECOVERIES: LazyLock<DashMap<&'static str, (Duration, Duration)>> =
+    LazyLock::new(DashMap::new);
+
+static HISTOGRAMS: LazyLock<DashMap<&'static str, Vec<(Duration, u32)>>> =
+    LazyLock::new(DashMap::new);
+
+static CLOCK_SKEWS: LazyLock<DashMap<&'static str, ClockSkew>> = LazyLock::new(DashMap::new);
+
+static SLEEP_STATS: LazyLock<DashMap<&'static str, SleepStats>> = LazyLock::new(DashMap::new);
+
+#[derive(Default)]
+struct BlockState {
+    releases: HashMap<&'static str, u64>,
+    /// How many `maybe_block!`/`maybe_block_async!` calls wait on each tag; its `releases`
+    /// entry is dropped when this reaches zero.
+    waiters: HashMap<&'static str, usize>,
+    /// Wakers of pending `BlockFuture`s, keyed by their id.
+    wakers: HashMap<&'static str, Vec<(u64, Waker)>>,
+}
+
+impl BlockState {
+    /// Starts a wait on `tag`, returning the release count to wait past.
+    fn join(&mut self, tag: &'static str) -> u64 {
+        *self.waiters.entry(tag).or_insert(0) += 1;
+        *self.releases.entry(tag).or_insert(0)
+    }
+
+    /// Ends a wait started with `join`, dropping the waker of the future `id`, if any.
+    fn leave(&mut self, tag: &'static str, id: Option<u64>) {
+        if let (Some(id), Some(wakers)) = (id, self.wakers.get_mut(tag)) {
+            wakers.retain(|(waiter, _)| *waiter != id);
+            if wakers.is_empty() {
+                self.wakers.remove(tag);
+            }
+        }
+        if let Some(waiters) = self.waiters.get_mut(tag) {
+            *waiters -= 1;
+            if *waiters == 0 {
+                self.waiters.remove(tag);
+                self.releases.remove(tag);
+            }
+        }
+    }
+}
+
+static NEXT_BLOCK_ID: AtomicU64 = AtomicU64::new(0);
+
+static BLOCKS: LazyLock<(Mutex<BlockState>, Condvar)> =
+    LazyLock::new(|| (Mutex::new(BlockState::default()), Condvar::new()));
+
+struct Rendezvous {
+    waiter: &'static str,
+    signal: &'static str,
+    timeout: Duration,
+    arrived: bool,
+}
+
+static BARRIERS: LazyLock<(Mutex<Vec<Rendezvous>>, Condvar)> =
+    LazyLock::new(|| (Mutex::new(Vec::new()), Condvar::new()));
+
+static BARRIERS_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+static STEPS: LazyLock<(Mutex<u64>, Condvar)> = LazyLock::new(|| (Mutex::new(0), Condvar::new()));
+
+static ENABLED_AT: LazyLock<DashMap<&'static str, Instant>> = LazyLock::new(DashMap::new);
+
+static DEFAULT_ACTION: Mutex<Option<Action>> = Mutex::new(None);
+
+/// A callback registered with [`configure_per_thread`].
+pub type PerThreadAction = dyn Fn(ThreadId) -> Action + Send + Sync;
+
+/// What a tag does when it fires, as set by one of the action setters. A tag holds at most
+/// one; setting another replaces it.
+#[derive(Clone)]
+pub(crate) enum TagAction {
+    /// Set by `enable_failpoint_with_action`, `configure_chain` and `with_action`.
+    Chain(Vec<Action>),
+    /// Set by `configure_escalation`; sorted by offset.
+    Escalation(Vec<(Duration, Action)>),
+    /// Set by `configure_per_thread`.
+    PerThread(Arc<PerThreadAction>),
+}
+
+static ACTIONS: LazyLock<DashMap<&'static str, TagAction>> = LazyLock::new(DashMap::new);
+
+/// A callback registered with [`on_state_change`].
+pub type StateListener = dyn Fn(&str, bool) + Send + Sync;
+
+static STATE_LISTENERS: RwLock<Vec<Arc<StateListener>>> = RwLock::new(Vec::new());
+
+static GROUPS: LazyLock<DashMap<&'static str, &'static str>> = LazyLock::new(DashMap::new);
+
+static SINGLE_FIRE_GROUPS: LazyLock<DashSet<&'static str>> = LazyLock::new(DashSet::new);
+
+thread_local! {
+    static SUPPRESSED: Cell<bool> = const { Cell::new(false) };
+    static SUPPRESS_DEPTH: Cell<u32> = const { Cell::new(0) };
+    static DROP_FAILURES: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    static SLOW_STARTS: RefCell<HashMap<&'static str, Instant>> = RefCell::new(HashMap::new());
+    static REQUEST_WINNERS: RefCell<Option<HashMap<&'static str, &'static str>>> =
+        const { RefCell::new(None) };
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Updates of `ENABLED_COUNT` and locks of `BLOCKS` made by this thread, so tests can
+    /// check that bulk calls batch them.
+    pub(crate) static COUNT_UPDATES: Cell<u32> = const { Cell::new(0) };
+    pub(crate) static RELEASE_LOCKS: Cell<u32> = const { Cell::new(0) };
+}
+
+static PROBABILITIES: LazyLock<DashMap<&'static str, f64>> = LazyLock::new(DashMap::new);
+
+static DECAYS: LazyLock<DashMap<&'static str, (f64, Duration)>> = LazyLock::new(DashMap::new);
+
+static PROCESS_SEED: LazyLock<AtomicU64> =
+    LazyLock::new(|| AtomicU64::new(std::process::id() as u64));
+
+static HITS: LazyLock<DashMap<&'static str, AtomicU64>> = LazyLock::new(DashMap::new);
+
+/// Like `HITS`, but never reset, so exported counters only go up.
+static TOTAL_HITS: LazyLock<DashMap<&'static str, AtomicU64>> = LazyLock::new(DashMap::new);
+
+static POLL_COUNTS: LazyLock<DashMap<&'static str, AtomicU64>> = LazyLock::new(DashMap::new);
+
+static FAILURE_COUNTS: LazyLock<DashMap<&'static str, u64>> = LazyLock::new(DashMap::new);
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+static RUNTIME_CHAOS: AtomicBool = AtomicBool::new(false);
+
+static TIME_SCALE: AtomicU64 = AtomicU64::new(1.0f64.to_bits());
+
+static MIN_SLEEP_NANOS: AtomicU64 = AtomicU64::new(0);
+
+static INJECTION_BUDGET: AtomicU64 = AtomicU64::new(u64::MAX);
+
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+static RNG_STATE: LazyLock<AtomicU64> =
+    LazyLock::new(|| AtomicU64::new(RandomState::new().hash_one(Instant::now())));
+
+pub(crate) fn tag_states() -> BTreeMap<&'static str, TagState> {
+    let mut tags: BTreeMap<&'static str, TagState> = BTreeMap::new();
+    for tag in FAILPOINTS.iter() {
+        tags.entry(*tag).or_default().enabled = true;
+    }
+    for entry in SLEEP_OVERRIDES.iter() {
+        tags.entry(*entry.key()).or_default().sleep = Some(*entry.value());
+    }
+    for entry in ACTIONS.iter() {
+        let state = tags.entry(*entry.key()).or_default();
+        match entry.value() {
+            TagAction::Chain(actions) => state.chain = Some(actions.clone()),
+            TagAction::Escalation(schedule) => state.escalation = Some(schedule.clone()),
+            TagAction::PerThread(_) => state.per_thread = true,
+        }
+    }
+    for entry in COOLDOWNS.iter() {
+        tags.entry(*entry.key()).or_default().cooldown = Some(entry.value().period);
+    }
+    for entry in LIMITS.iter() {
+        let limit = entry.value();
+        tags.entry(*entry.key()).or_default().limit = Some((limit.remaining_hits, limit.deadline));
+    }
+    for entry in DIRECTIONS.iter() {
+        tags.entry(*entry.key()).or_default().direction = Some(*entry.value());
+    }
+    for entry in WRITE_FRACTIONS.iter() {
+        tags.entry(*entry.key()).or_default().write_fraction = Some(*entry.value());
+    }
+    for entry in GROUPS.iter() {
+        tags.entry(*entry.key()).or_default().group = Some(*entry.value());
+    }
+    for entry in PROBABILITIES.iter() {
+        tags.entry(*entry.key()).or_default().probability = Some(*entry.value());
+    }
+    for entry in DECAYS.iter() {
+        tags.entry(*entry.key()).or_default().decay = Some(*entry.value());
+    }
+    for entry in RECOVERIES.iter() {
+        tags.entry(*entry.key()).or_default().recovery = Some(*entry.value());
+    }
+    for entry in HISTOGRAMS.iter() {
+        tags.entry(*entry.key()).or_default().histogram = Some(entry.value().clone());
+    }
+    for entry in CLOCK_SKEWS.iter() {
+        tags.entry(*entry.key()).or_default().clock_skew = Some(*entry.value());
+    }
+    for entry in CHILDREN.iter() {
+        tags.entry(*entry.key()).or_default().children = Some(entry.value().clone());
+    }
+    tags
+}
+
+/// Returns whether `tag` is enabled.
+///
+/// The lookup borrows `tag` as a `str` key and never allocates, so it is cheap enough for
+/// hot paths.
 pub fn is_failpoint_enabled(tag: &str) -> bool {
-    FAILPOINTS.contains(tag)
+    !SUPPRESSED.with(Cell::get) && SUPPRESS_DEPTH.with(Cell::get) == 0 && FAILPOINTS.contains(tag)
+}
+
+/// Suppresses chaos on the calling thread until the matching [`pop_suppress`].
+///
+/// Unlike [`suppress_chaos_on_current_thread`] this keeps a per-thread depth, so nested
+/// pushes from independent code (a library guarding its own initialization, called from a
+/// test that also suppresses) compose: failpoints stay disabled on the thread until every
+/// push has been popped.
+pub fn push_suppress() {
+    SUPPRESS_DEPTH.with(|depth| depth.set(depth.get() + 1));
+}
+
+/// Pops one level pushed with [`push_suppress`]; extra pops are ignored.
+pub fn pop_suppress() {
+    SUPPRESS_DEPTH.with(|depth| depth.set(depth.get().saturating_sub(1)));
 }
 
+/// Makes every failpoint look disabled on the calling thread until
+/// [`resume_chaos_on_current_thread`] is called.
+///
+/// Lets a coordinator thread run chaos-free while worker threads still see the enabled
+/// failpoints. Only evaluations on this thread are affected; enabling and disabling tags
+/// from it works as usual.
+pub fn suppress_chaos_on_current_thread() {
+    SUPPRESSED.with(|suppressed| suppressed.set(true));
+}
+
+/// Undoes [`suppress_chaos_on_current_thread`] for the calling thread.
+pub fn resume_chaos_on_current_thread() {
+    SUPPRESSED.with(|suppressed| suppressed.set(false));
+}
+
+#[track_caller]
 pub fn enable_failpoint(tag: &'static str) {
-    FAILPOINTS.insert(tag);
+    let origin = Location::caller();
+    enable_one(tag, origin);
+    for child in descendants(tag) {
+        enable_one(child, origin);
+    }
+}
+
+/// Enables every tag in `tags`, with the same effect as calling `enable_failpoint` on each.
+///
+/// Meant for setting up large scenarios: the caller location and the state-change
+/// listeners are looked up once for the whole batch, and listeners are notified only after
+/// every tag (and its children) is enabled.
+#[track_caller]
+pub fn enable_failpoints(tags: &[&'static str]) {
+    let origin = Location::caller();
+    let mut changed = Vec::new();
+    for &tag in tags {
+        for tag in std::iter::once(tag).chain(descendants(tag)) {
+            if insert_enabled(tag, origin) {
+                changed.push(tag);
+            }
+        }
+    }
+    update_enabled_count(changed.len(), true);
+    notify_state_changes(&changed, true);
+}
+
+fn enable_one(tag: &'static str, origin: &'static Location<'static>) {
+    if insert_enabled(tag, origin) {
+        update_enabled_count(1, true);
+        notify_state_change(tag, true);
+    }
+}
+
+fn update_enabled_count(changed: usize, enabled: bool) {
+    if changed == 0 {
+        return;
+    }
+    if enabled {
+        ENABLED_COUNT.fetch_add(changed, Ordering::Relaxed);
+    } else {
+        ENABLED_COUNT.fetch_sub(changed, Ordering::Relaxed);
+    }
+    #[cfg(test)]
+    COUNT_UPDATES.with(|updates| updates.set(updates.get() + 1));
+}
+
+/// Returns how many failpoints are enabled, without walking the enabled set.
+pub fn enabled_failpoint_count() -> usize {
+    ENABLED_COUNT.load(Ordering::Relaxed)
+}
+
+/// Enables `tag` without notifying listeners; returns whether it was newly enabled.
+fn insert_enabled(tag: &'static str, origin: &'static Location<'static>) -> bool {
+    LIMITS.remove(tag);
+    DIRECTIONS.remove(tag);
+    ORIGINS.insert(tag, origin);
+    let inserted = FAILPOINTS.insert(tag);
+    if inserted {
+        ENABLED_AT.insert(tag, Instant::now());
+    }
+    inserted
+}
+
+/// Registers `child` under `parent`, so enabling or disabling `parent` does the same to
+/// `child`.
+///
+/// Propagation is recursive: a child's own children follow too, and cycles are harmless.
+/// It happens at the moment the parent is enabled or disabled, so a child registered
+/// while its parent is enabled is armed on the parent's next enable. Children can still be
+/// enabled and disabled on their own, and per-tag settings such as a hit limit or an I/O
+/// direction given when enabling the parent apply to the parent only.
+pub fn register_child(parent: &'static str, child: &'static str) {
+    let mut children = CHILDREN.entry(parent).or_default();
+    if !children.contains(&child) {
+        children.push(child);
+    }
+}
+
+fn descendants(tag: &str) -> Vec<&'static str> {
+    let mut found: Vec<&'static str> = Vec::new();
+    let mut pending: Vec<&'static str> = match CHILDREN.get(tag) {
+        Some(children) => children.clone(),
+        None => return found,
+    };
+    while let Some(child) = pending.pop() {
+        if child == tag || found.contains(&child) {
+            continue;
+        }
+        found.push(child);
+        if let Some(grandchildren) = CHILDREN.get(child) {
+            pending.extend(grandchildren.iter().copied());
+        }
+    }
+    found
+}
+
+/// Registers `listener` to be called with `(tag, true)` whenever a tag becomes enabled and
+/// `(tag, false)` whenever it becomes disabled.
+///
+/// Only real transitions are reported; enabling an already enabled tag is silent. Listeners
+/// run on the thread that made the change, after it took effect and without any lock held,
+/// so they may enable or disable tags (or register more listeners) themselves. Listeners
+/// stay registered for the life of the process.
+pub fn on_state_change(listener: Box<StateListener>) {
+    STATE_LISTENERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Arc::from(listener));
+}
+
+fn notify_state_change(tag: &str, enabled: bool) {
+    notify_state_changes(&[tag], enabled);
+}
+
+fn notify_state_changes(tags: &[&str], enabled: bool) {
+    if tags.is_empty() {
+        return;
+    }
+    let listeners = STATE_LISTENERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    for tag in tags {
+        for listener in &listeners {
+            listener(tag, enabled);
+        }
+    }
+}
+
+/// Enables `tag` so that it fires on every evaluation until it is disabled.
+///
+/// This is the plain `enable_failpoint` behavior made explicit: any cooldown configured
+/// for the tag is removed so nothing rate-limits it.
+#[track_caller]
+pub fn enable_failpoint_sustained(tag: &'static str) {
+    clear_cooldown(tag);
+    enable_failpoint(tag);
+}
+
+/// Enables `tag` only if this process falls within `fraction` of all processes.
+///
+/// The roll is derived from the process seed (the process id unless overridden with
+/// [`set_process_seed`]) and the tag, so every call in the same process makes the same
+/// decision, while replicas with different ids split roughly by `fraction`. Returns whether
+/// this process participates.
+#[track_caller]
+pub fn enable_failpoint_on_fraction(tag: &'static str, fraction: f64) -> bool {
+    let roll = unit_interval(mix64(PROCESS_SEED.load(Ordering::Relaxed) ^ hash_str(tag)));
+    let participates = roll < fraction;
+    if participates {
+        enable_failpoint(tag);
+    }
+    participates
 }
 
+/// Enables `tag` only in builds with debug assertions, as a guard against arming chaos in
+/// production by accident.
+///
+/// The check is `cfg!(debug_assertions)` as seen by this crate, so in a release build the
+/// call is a no-op. Returns whether the tag was enabled.
+#[track_caller]
+pub fn enable_failpoint_if_debug(tag: &'static str) -> bool {
+    if cfg!(debug_assertions) {
+        enable_failpoint(tag);
+    }
+    cfg!(debug_assertions)
+}
+
+/// Enables `tag` until it has fired `max_hits` times or `max_duration` has elapsed,
+/// whichever comes first, then disables it.
+///
+/// The last allowed hit still fires; the tag is disabled right after it. The time limit is
+/// checked when the tag is evaluated, so an expired tag stays listed as enabled until its
+/// next evaluation. Disabling or re-enabling the tag drops the limit. A `max_duration` too
+/// large to add to the current time, such as `Duration::MAX`, means no time limit. A
+/// `max_hits` of zero disables the tag without enabling it first.
+#[track_caller]
+pub fn enable_failpoint_limited(tag: &'static str, max_hits: u64, max_duration: Duration) {
+    if max_hits == 0 {
+        disable_failpoint(tag);
+        return;
+    }
+    enable_failpoint(tag);
+    LIMITS.insert(
+        tag,
+        Limit {
+            remaining_hits: max_hits,
+            deadline: Instant::now().checked_add(max_duration),
+        },
+    );
+}
+
+/// Enables `tag` for one I/O direction only, so `maybe_fail_read!` and
+/// `maybe_fail_write!` on the same tag can be impaired independently.
+///
+/// Other macros evaluating the tag ignore the direction. A later `enable_failpoint`
+/// restores both directions.
+#[track_caller]
+pub fn enable_failpoint_direction(tag: &'static str, direction: Direction) {
+    enable_failpoint(tag);
+    DIRECTIONS.insert(tag, direction);
+}
+
+/// Enables `tag` and makes every macro evaluating it perform `action` instead of its own.
+///
+/// This is the canonical way to choose what a failpoint does; see [`Action`] for how each
+/// macro reacts to it. A tag has a single configured action: this replaces any chain,
+/// escalation or per-thread selector set for the tag, and later setters replace it in
+/// turn. The action stays configured while the tag is disabled and re-enabled, until it
+/// is replaced or removed with [`clear_action`] or [`clear_all_failpoints`]. Child tags are
+/// enabled with their own actions.
+#[track_caller]
+pub fn enable_failpoint_with_action(tag: &'static str, action: Action) {
+    ACTIONS.insert(tag, TagAction::Chain(vec![action]));
+    enable_failpoint(tag);
+}
+
+/// Removes the action configured for `tag`, whichever setter configured it, so its macros
+/// fall back to the default action or their own behavior.
+pub fn clear_action(tag: &str) {
+    ACTIONS.remove(tag);
+}
+
+pub(crate) fn tag_action(tag: &str) -> Option<TagAction> {
+    ACTIONS.get(tag).map(|action| action.clone())
+}
+
+pub(crate) fn restore_tag_action(tag: &'static str, action: Option<TagAction>) {
+    match action {
+        Some(action) => ACTIONS.insert(tag, action),
+        None => ACTIONS.remove(tag).map(|(_, action)| action),
+    };
+}
+
+pub fn direction_allows(tag: &str, direction: Direction) -> bool {
+    DIRECTIONS
+        .get(tag)
+        .is_none_or(|enabled| enabled.allows(direction))
+}
+
+/// Returns whether the environment variable `var` is set to a truthy value: anything but
+/// empty, `0`, `false`, `no` or `off`, compared case-insensitively.
+///
+/// The variable is read on every call, not cached, so changing it takes effect on the next
+/// evaluation of `maybe_fail_env!`.
+pub fn env_allows(var: &str) -> bool {
+    std::env::var_os(var).is_some_and(|value| {
+        let value = value.to_string_lossy();
+        let value = value.trim();
+        !(value.is_empty()
+            || value == "0"
+            || ["false", "no", "off"]
+                .iter()
+                .any(|falsy| value.eq_ignore_ascii_case(falsy)))
+    })
+}
+
+/// Sets the process-wide generation that `maybe_fail_gen!` compares against.
+///
+/// Starts at 0. A test advances it as it moves between phases, so failures can be aimed at
+/// one phase while the tags stay enabled throughout.
+pub fn set_generation(generation: u64) {
+    GENERATION.store(generation, Ordering::Relaxed);
+}
+
+/// Returns the generation set with [`set_generation`].
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Overrides the process id used to seed [`enable_failpoint_on_fraction`].
+pub fn set_process_seed(seed: u64) {
+    PROCESS_SEED.store(seed, Ordering::Relaxed);
+}
+
+/// Disables `tag` and wakes any `maybe_block!`/`maybe_block_async!` waiting on it.
 pub fn disable_failpoint(tag: &str) {
-    FAILPOINTS.remove(tag);
+    disable_one(tag);
+    for child in descendants(tag) {
+        disable_one(child);
+    }
+}
+
+/// Disables every tag in `tags`, with the same effect as calling `disable_failpoint` on
+/// each.
+///
+/// Like [`enable_failpoints`], listeners are looked up once and notified after the whole
+/// batch is disabled, and blocked callers are released under a single lock.
+pub fn disable_failpoints(tags: &[&str]) {
+    let mut released: Vec<&str> = Vec::new();
+    let mut changed = Vec::new();
+    for &tag in tags {
+        released.push(tag);
+        if remove_enabled(tag) {
+            changed.push(tag);
+        }
+        for child in descendants(tag) {
+            released.push(child);
+            if remove_enabled(child) {
+                changed.push(child);
+            }
+        }
+    }
+    release_failpoints(&released);
+    update_enabled_count(changed.len(), false);
+    notify_state_changes(&changed, false);
+}
+
+fn disable_one(tag: &str) {
+    let removed = remove_enabled(tag);
+    release_failpoint(tag);
+    if removed {
+        update_enabled_count(1, false);
+        notify_state_change(tag, false);
+    }
+}
+
+/// Disables `tag` without notifying listeners; returns whether it was enabled.
+fn remove_enabled(tag: &str) -> bool {
+    let removed = FAILPOINTS.remove(tag);
+    ORIGINS.remove(tag);
+    ENABLED_AT.remove(tag);
+    POLL_COUNTS.remove(tag);
+    FAILURE_COUNTS.remove(tag);
+    LIMITS.remove(tag);
+    DIRECTIONS.remove(tag);
+    removed.is_some()
+}
+
+/// Disables every failpoint and forgets all per-tag state.
+///
+/// Disabling releases anything blocked on the tags. The stores cleared are:
+/// - configured actions (chains, escalations and per-thread actions)
+/// - cooldowns, hit limits, direction gates, groups and child registrations
+/// - probabilities, decays and write fractions
+/// - sleep overrides, latency histograms, recovery curves and clock skews
+/// - hit and poll counts, and sleep statistics
+///
+/// Process-wide settings such as the seed, dry run and the default action are left as
+/// they are.
+pub fn clear_all_failpoints() {
+    let tags: Vec<&'static str> = FAILPOINTS.iter().map(|tag| *tag).collect();
+    disable_failpoints(&tags);
+    COOLDOWNS.clear();
+    WRITE_FRACTIONS.clear();
+    SLEEP_OVERRIDES.clear();
+    HISTOGRAMS.clear();
+    RECOVERIES.clear();
+    CLOCK_SKEWS.clear();
+    SLEEP_STATS.clear();
+    GROUPS.clear();
+    SINGLE_FIRE_GROUPS.clear();
+    PROBABILITIES.clear();
+    DECAYS.clear();
+    HITS.clear();
+    POLL_COUNTS.clear();
+    LIMITS.clear();
+    DIRECTIONS.clear();
+    ACTIONS.clear();
+    CHILDREN.clear();
+}
+
+/// Calls [`clear_all_failpoints`] when dropped; used by `#[chaos_test]`.
+pub struct ClearAllOnDrop;
+
+impl Drop for ClearAllOnDrop {
+    fn drop(&mut self) {
+        clear_all_failpoints();
+    }
+}
+
+/// Disables the wrapped tag when dropped.
+pub struct DisableOnDrop(pub &'static str);
+
+impl Drop for DisableOnDrop {
+    fn drop(&mut self) {
+        disable_failpoint(self.0);
+    }
+}
+
+/// Returns the source location that enabled `tag`, if it is currently enabled.
+///
+/// Helpful for tracking down which test left a failpoint enabled.
+pub fn failpoint_origin(tag: &str) -> Option<&'static Location<'static>> {
+    ORIGINS.get(tag).map(|origin| *origin)
+}
+
+/// Turns the runtime master switch checked by the `*_runtime!` macros on or off.
+///
+/// The switch is off by default. The compile-time gated macros such as `maybe_fail!` ignore
+/// it.
+pub fn set_runtime_chaos(enabled: bool) {
+    RUNTIME_CHAOS.store(enabled, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn is_runtime_chaos_enabled() -> bool {
+    RUNTIME_CHAOS.load(Ordering::Relaxed)
+}
+
+/// Turns dry-run mode on or off for the whole process.
+///
+/// While dry-run is on, an enabled failpoint logs the action it would have taken
+/// (when the `log` feature is enabled) and then behaves as if it were disabled.
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Makes `tag` stay quiet for `period` after each time it fires.
+///
+/// The cooldown only starts when the failpoint actually fires, so evaluations
+/// while the tag is disabled or in dry-run mode do not consume it. Once `period`
+/// has elapsed the next evaluation fires again and starts a new cooldown.
+pub fn configure_cooldown(tag: &'static str, period: Duration) {
+    COOLDOWNS.insert(
+        tag,
+        Cooldown {
+            period,
+            last_fire: None,
+        },
+    );
+}
+
+pub fn clear_cooldown(tag: &str) {
+    COOLDOWNS.remove(tag);
+}
+
+/// Claims one hit from `tag`'s limit. Returns `None` if the limit denies the hit, and
+/// otherwise whether the hit used the limit up; the caller then disables the tag, or
+/// hands the hit back with [`refund_limit`] if a later check denies the fire.
+fn take_limit(tag: &str) -> Option<bool> {
+    match LIMITS.get_mut(tag) {
+        None => Some(false),
+        Some(mut limit) => {
+            if limit
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                drop(limit);
+                disable_failpoint(tag);
+                None
+            } else if limit.remaining_hits == 0 {
+                None
+            } else {
+                limit.remaining_hits -= 1;
+                Some(limit.remaining_hits == 0)
+            }
+        }
+    }
+}
+
+fn refund_limit(tag: &str) {
+    if let Some(mut limit) = LIMITS.get_mut(tag) {
+        limit.remaining_hits += 1;
+    }
+}
+
+fn cooldown_allows(tag: &str) -> bool {
+    let Some(mut cooldown) = COOLDOWNS.get_mut(tag) else {
+        return true;
+    };
+    let now = Instant::now();
+    if let Some(last) = cooldown.last_fire
+        && now.duration_since(last) < cooldown.period
+    {
+        return false;
+    }
+    cooldown.last_fire = Some(now);
+    true
+}
+
+/// Adds `tag` to `group`, replacing any group it was in before.
+pub fn set_failpoint_group(tag: &'static str, group: &'static str) {
+    GROUPS.insert(tag, group);
+}
+
+/// Limits `group` to a single firing member per request when `enabled`.
+///
+/// Requests are delimited on the current thread with [`begin_request`] and
+/// [`end_request`]. Outside a request, members of the group fire independently.
+pub fn set_single_fire_per_group(group: &'static str, enabled: bool) {
+    if enabled {
+        SINGLE_FIRE_GROUPS.insert(group);
+    } else {
+        SINGLE_FIRE_GROUPS.remove(group);
+    }
+}
+
+/// Starts a request on the current thread.
+///
+/// For every single-fire group, one currently enabled member is picked at random and
+/// only that member can fire until [`end_request`] is called.
+pub fn begin_request() {
+    let mut members: HashMap<&'static str, Vec<&'static str>> = HashMap::new();
+    for entry in GROUPS.iter() {
+        let (tag, group) = (*entry.key(), *entry.value());
+        if SINGLE_FIRE_GROUPS.contains(group) && is_failpoint_enabled(tag) {
+            members.entry(group).or_default().push(tag);
+        }
+    }
+    let winners = members
+        .into_iter()
+        .map(|(group, mut tags)| {
+            tags.sort_unstable();
+            let pick = (random_u64() % tags.len() as u64) as usize;
+            (group, tags[pick])
+        })
+        .collect();
+    REQUEST_WINNERS.with(|w| *w.borrow_mut() = Some(winners));
+}
+
+/// Ends the request started by [`begin_request`] on the current thread.
+pub fn end_request() {
+    REQUEST_WINNERS.with(|w| *w.borrow_mut() = None);
+}
+
+fn group_allows(tag: &str) -> bool {
+    let Some(group) = GROUPS.get(tag).map(|g| *g) else {
+        return true;
+    };
+    if !SINGLE_FIRE_GROUPS.contains(group) {
+        return true;
+    }
+    REQUEST_WINNERS.with(|w| match &*w.borrow() {
+        Some(winners) => winners.get(group) == Some(&tag),
+        None => true,
+    })
+}
+
+/// Makes `tag` fire on only a `probability` fraction of evaluations while enabled.
+///
+/// The roll uses the generator seeded by [`set_seed`] and happens before the cooldown
+/// check, so a failed roll does not start a cooldown.
+pub fn configure_probability(tag: &'static str, probability: f64) {
+    PROBABILITIES.insert(tag, probability.clamp(0.0, 1.0));
+}
+
+pub fn clear_probability(tag: &str) {
+    PROBABILITIES.remove(tag);
+}
+
+/// Makes the fire probability of `tag` decay exponentially with its time enabled.
+///
+/// The probability is `initial_prob * 0.5^(t / half_life)`, where `t` is the time since the
+/// tag was last enabled, so it starts at `initial_prob` and halves every `half_life`,
+/// modeling a system that recovers. It is rolled on every evaluation with the generator
+/// seeded by [`set_seed`], after any fixed probability from [`configure_probability`].
+/// Re-enabling the tag restarts the decay.
+pub fn configure_decay(tag: &'static str, initial_prob: f64, half_life: Duration) {
+    DECAYS.insert(tag, (initial_prob.clamp(0.0, 1.0), half_life));
+}
+
+pub fn clear_decay(tag: &str) {
+    DECAYS.remove(tag);
+}
+
+fn probability_allows(tag: &str) -> bool {
+    let fixed = match PROBABILITIES.get(tag).map(|p| *p) {
+        Some(probability) => random_f64() < probability,
+        None => true,
+    };
+    fixed && decay_allows(tag)
+}
+
+fn decay_allows(tag: &str) -> bool {
+    let Some((initial, half_life)) = DECAYS.get(tag).map(|decay| *decay) else {
+        return true;
+    };
+    let elapsed = ENABLED_AT
+        .get(tag)
+        .map(|at| at.elapsed())
+        .unwrap_or_default();
+    let probability = if half_life.is_zero() {
+        0.0
+    } else {
+        initial * 0.5f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64())
+    };
+    random_f64() < probability
+}
+
+/// Records an evaluation of `tag` without deciding whether it fires: registers the tag
+/// for [`known_failpoints`] and takes part in any active [`failpoint_barrier`].
+///
+/// [`should_fire`] does this itself. Macros with a gate of their own, such as an
+/// environment variable or a generation, call this before checking the gate so gated-out
+/// evaluations still register, then call [`should_fire_registered`] once it passes.
+pub fn register_tag(tag: &'static str) {
+    if !KNOWN_TAGS.contains(tag) {
+        KNOWN_TAGS.insert(tag);
+    }
+    if BARRIERS_ACTIVE.load(Ordering::Relaxed) {
+        rendezvous(tag);
+    }
+}
+
+/// Decides whether a macro should carry out `action` for `tag`.
+///
+/// Every evaluation that returns `true` counts as a hit for [`failpoint_hits`].
+#[track_caller]
+pub fn should_fire(tag: &'static str, action: &'static str) -> bool {
+    register_tag(tag);
+    should_fire_registered(tag, action)
+}
+
+/// Like [`should_fire`], for a tag whose evaluation was already recorded with
+/// [`register_tag`].
+#[track_caller]
+pub fn should_fire_registered(tag: &'static str, action: &'static str) -> bool {
+    let fired = if session::is_active() {
+        let (evaluation, replayed) = session::next_evaluation(tag);
+        let fired = match replayed {
+            Some(true) => {
+                count_hit(tag);
+                true
+            }
+            Some(false) => false,
+            None => decide(tag, action),
+        };
+        if fired {
+            session::record_fire(tag, action, evaluation);
+        }
+        fired
+    } else {
+        decide(tag, action)
+    };
+    if fired {
+        injections::record_injection(tag, action, Location::caller());
+        if INTERACTIVE.load(Ordering::Relaxed) {
+            wait_for_step();
+        }
+    }
+    fired
+}
+
+/// Turns interactive stepping on or off, process-wide.
+///
+/// While on, every failpoint that fires blocks the evaluating thread until [`step`] lets
+/// it through, before the injected failure happens, so a debugger or test can single-step
+/// through injected failures one at a time. Evaluations that don't fire never block.
+/// Turning it off releases every blocked hit and discards unused steps.
+pub fn set_interactive(enabled: bool) {
+    INTERACTIVE.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        let (steps, released) = &*STEPS;
+        *steps.lock().unwrap_or_else(|e| e.into_inner()) = 0;
+        released.notify_all();
+    }
+}
+
+/// Lets one blocked failpoint hit proceed in interactive mode.
+///
+/// Steps are banked: calling `step` before any hit is blocked lets the next hit through
+/// without waiting, so a test can step from another thread without racing the hit. When
+/// several hits are blocked an arbitrary one proceeds.
+pub fn step() {
+    let (steps, released) = &*STEPS;
+    *steps.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+    released.notify_one();
+}
+
+fn wait_for_step() {
+    let (steps, released) = &*STEPS;
+    let mut steps = released
+        .wait_while(steps.lock().unwrap_or_else(|e| e.into_inner()), |steps| {
+            *steps == 0 && INTERACTIVE.load(Ordering::Relaxed)
+        })
+        .unwrap_or_else(|e| e.into_inner());
+    *steps = steps.saturating_sub(1);
 }
 
+fn decide(tag: &'static str, action: &str) -> bool {
+    if !is_failpoint_enabled(tag) || !group_allows(tag) {
+        return false;
+    }
+    if is_dry_run() {
+        #[cfg(feature = "log")]
+        log::info!("chaos dry run: failpoint '{}' would {}", tag, action);
+        #[cfg(not(feature = "log"))]
+        let _ = action;
+        return false;
+    }
+    if !probability_allows(tag) || !cooldown_allows(tag) {
+        return false;
+    }
+    let Some(exhausted) = take_limit(tag) else {
+        return false;
+    };
+    if !budget_allows() {
+        refund_limit(tag);
+        return false;
+    }
+    if exhausted {
+        disable_failpoint(tag);
+    }
+    count_hit(tag);
+    true
+}
+
+/// Caps the number of injections that may fire across all tags and threads to `n`.
+///
+/// The budget is a single atomic counter taken by each fire after every other check has
+/// passed, so concurrent hits never overspend it. Once it reaches zero every failpoint
+/// behaves as disabled until a new budget is set or it's removed with
+/// [`clear_global_injection_budget`]. Replayed fires don't draw from it.
+pub fn set_global_injection_budget(n: u64) {
+    INJECTION_BUDGET.store(n, Ordering::Relaxed);
+}
+
+/// Removes the budget set with [`set_global_injection_budget`].
+pub fn clear_global_injection_budget() {
+    INJECTION_BUDGET.store(u64::MAX, Ordering::Relaxed);
+}
+
+fn budget_allows() -> bool {
+    INJECTION_BUDGET
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| match left {
+            u64::MAX => Some(u64::MAX),
+            0 => None,
+            left => Some(left - 1),
+        })
+        .is_ok()
+}
+
+fn count_hit(tag: &'static str) {
+    HITS.entry(tag).or_default().fetch_add(1, Ordering::Relaxed);
+    TOTAL_HITS
+        .entry(tag)
+        .or_default()
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// Evaluates `tag` for `#[chaos_scatter]` and, if it fires, picks which of `sites`
+/// statements fails.
+pub fn scatter_site(tag: &'static str, sites: usize) -> Option<usize> {
+    if sites == 0 || !should_fire(tag, "fail") || execute_chain(tag) == ChainOutcome::Proceed {
+        return None;
+    }
+    Some((random_u64() % sites as u64) as usize)
+}
+
+/// Counts one poll of `tag` and returns the new count, starting at 1.
+///
+/// Only polls made while the tag is enabled are counted, and the count restarts when the
+/// tag is disabled.
+pub fn next_poll(tag: &'static str) -> u64 {
+    if !is_failpoint_enabled(tag) {
+        return 0;
+    }
+    POLL_COUNTS
+        .entry(tag)
+        .or_default()
+        .fetch_add(1, Ordering::Relaxed)
+        + 1
+}
+
+/// Counts one more failure of `maybe_fail_times!` for `tag`, disabling the tag once it has
+/// failed `times` times since it was enabled. Returns `false` without counting if `times`
+/// is zero.
+pub fn fail_times_allows(tag: &'static str, times: u64) -> bool {
+    if times == 0 {
+        disable_failpoint(tag);
+        return false;
+    }
+    let failures = {
+        let mut failures = FAILURE_COUNTS.entry(tag).or_insert(0);
+        *failures += 1;
+        *failures
+    };
+    if failures >= times {
+        disable_failpoint(tag);
+    }
+    true
+}
+
+/// Returns a `'static` copy of a runtime tag string, reusing the tag of a known or enabled
+/// failpoint or of an earlier call and leaking a new copy only for tags not seen before.
+pub(crate) fn intern_tag(tag: &str) -> &'static str {
+    for tags in [&*KNOWN_TAGS, &*FAILPOINTS, &*INTERNED_TAGS] {
+        if let Some(known) = tags.get(tag) {
+            return *known;
+        }
+    }
+    let leaked: &'static str = Box::leak(tag.to_owned().into_boxed_str());
+    INTERNED_TAGS.insert(leaked);
+    leaked
+}
+
+/// Returns every tag that has been evaluated by a failpoint macro so far, sorted.
+///
+/// Tags are registered on their first evaluation whether or not they are enabled, so a
+/// control UI can list the available injection points. Tags whose code hasn't run yet
+/// aren't known unless they were declared with `define_failpoints!` (`inventory` feature).
+/// Registration is never undone, not even by [`clear_all_failpoints`].
+///
+/// The order is lexicographic regardless of which threads registered the tags or when,
+/// so the result can be compared against golden files.
+pub fn known_failpoints() -> Vec<String> {
+    register_static_failpoints();
+    let mut tags: Vec<String> = KNOWN_TAGS.iter().map(|tag| tag.to_string()).collect();
+    tags.sort();
+    tags
+}
+
+#[cfg(feature = "inventory")]
+pub use inventory;
+
+/// A tag declared with `define_failpoints!`, collected at link time.
+#[cfg(feature = "inventory")]
+pub struct StaticFailpoint {
+    pub tag: &'static str,
+}
+
+#[cfg(feature = "inventory")]
+inventory::collect!(StaticFailpoint);
+
+fn register_static_failpoints() {
+    #[cfg(feature = "inventory")]
+    {
+        static REGISTERED: std::sync::Once = std::sync::Once::new();
+        REGISTERED.call_once(|| {
+            for declared in inventory::iter::<StaticFailpoint> {
+                KNOWN_TAGS.insert(declared.tag);
+            }
+        });
+    }
+}
+
+/// Returns the currently enabled tags in lexicographic order.
+///
+/// The underlying set is sharded and iterates in no particular order, so the tags are
+/// sorted before returning; concurrent enables and disables only change which tags are
+/// listed, never their order.
+pub fn enabled_failpoints() -> Vec<&'static str> {
+    let mut tags: Vec<&'static str> = FAILPOINTS.iter().map(|tag| *tag).collect();
+    tags.sort_unstable();
+    tags
+}
+
+/// Renders one line per tag that is known, enabled or has fired, as
+/// `<tag>: enabled|disabled, <hits> hits`.
+///
+/// Lines are sorted by tag, so the report is deterministic for a given state and suits
+/// golden-file tests.
+pub fn activity_report() -> String {
+    register_static_failpoints();
+    let hits = hit_counts();
+    let mut tags: BTreeSet<&'static str> = KNOWN_TAGS.iter().map(|tag| *tag).collect();
+    tags.extend(FAILPOINTS.iter().map(|tag| *tag));
+    tags.extend(hits.keys().copied());
+
+    let mut report = String::new();
+    for tag in tags {
+        let state = if FAILPOINTS.contains(tag) {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        let hits = hits.get(tag).copied().unwrap_or(0);
+        report.push_str(&format!("{tag}: {state}, {hits} hits\n"));
+    }
+    report
+}
+
+pub(crate) fn hit_counts() -> BTreeMap<&'static str, u64> {
+    HITS.iter()
+        .map(|entry| (*entry.key(), entry.value().load(Ordering::Relaxed)))
+        .collect()
+}
+
+/// Like [`hit_counts`], but counting every hit since the process started.
+#[cfg(feature = "metrics")]
+pub(crate) fn total_hit_counts() -> BTreeMap<&'static str, u64> {
+    TOTAL_HITS
+        .iter()
+        .map(|entry| (*entry.key(), entry.value().load(Ordering::Relaxed)))
+        .collect()
+}
+
+/// Returns how many times `tag` has fired since the last reset.
+pub fn failpoint_hits(tag: &str) -> u64 {
+    HITS.get(tag)
+        .map(|hits| hits.load(Ordering::Relaxed))
+        .unwrap_or(0)
+}
+
+pub fn reset_failpoint_count(tag: &str) {
+    HITS.remove(tag);
+}
+
+/// Resets the hit counters of every tag starting with `prefix`, e.g. `"storage::"`.
+pub fn reset_failpoint_count_prefix(prefix: &str) {
+    HITS.retain(|tag, _| !tag.starts_with(prefix));
+}
+
+/// Seeds the random generator used by randomized failpoints.
+///
+/// The generator is shared by the whole process, so the sequence is only
+/// reproducible when draws happen in the same order.
+pub fn set_seed(seed: u64) {
+    RNG_STATE.store(seed, Ordering::Relaxed);
+}
+
+pub fn random_u64() -> u64 {
+    let state = RNG_STATE
+        .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+        .wrapping_add(0x9E37_79B9_7F4A_7C15);
+    mix64(state)
+}
+
+// splitmix64 finalizer
+fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// FNV-1a as a `Hasher`, for `Hash` values that need a hash stable across Rust releases
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xCBF2_9CE4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01B3);
+        }
+    }
+}
+
+// FNV-1a, stable across runs unlike `DefaultHasher`
+fn hash_str(value: &str) -> u64 {
+    value.bytes().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+fn unit_interval(value: u64) -> f64 {
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Returns a random value in `[0, 1)`.
+pub fn random_f64() -> f64 {
+    unit_interval(random_u64())
+}
+
+/// Builds the serializer error `maybe_serde_fail!` returns, through `ser::Error::custom`.
+#[cfg(feature = "serde")]
+pub fn serde_ser_error<E: serde::ser::Error>(tag: &str) -> E {
+    E::custom(format_args!("injected serialization failure at '{tag}'"))
+}
+
+/// Builds the deserializer error `maybe_serde_fail!` returns, through `de::Error::custom`.
+#[cfg(feature = "serde")]
+pub fn serde_de_error<E: serde::de::Error>(tag: &str) -> E {
+    E::custom(format_args!("injected deserialization failure at '{tag}'"))
+}
+
+/// Returns whether `key` falls in the `sample_rate` fraction of keys selected for `tag`.
+///
+/// The tag and key are hashed with FNV-1a, so a key gets the same decision on every call
+/// and in every run, across Rust releases and independent of `set_seed`. Different tags
+/// select different subsets of keys.
+pub fn hashed_allows<K: Hash + ?Sized>(tag: &str, key: &K, sample_rate: f64) -> bool {
+    let mut hasher = FnvHasher::default();
+    tag.hash(&mut hasher);
+    key.hash(&mut hasher);
+    unit_interval(mix64(hasher.finish())) < sample_rate
+}
+
+/// Sets the fraction of the buffer `maybe_short_write!` reports as written for `tag`.
+///
+/// Without a configured fraction a random one is drawn on every short write.
+pub fn configure_write_fraction(tag: &'static str, fraction: f64) {
+    WRITE_FRACTIONS.insert(tag, fraction.clamp(0.0, 1.0));
+}
+
+pub fn short_write_len(tag: &str, len: usize) -> usize {
+    let fraction = WRITE_FRACTIONS
+        .get(tag)
+        .map(|f| *f)
+        .unwrap_or_else(random_f64);
+    ((len as f64 * fraction) as usize).min(len.saturating_sub(1))
+}
+
+/// Picks a random short read length for a buffer of `len` bytes: at least one byte, so the
+/// read isn't mistaken for end of file, and fewer than `len` when `len` is above one.
+pub fn truncated_read_len(len: usize) -> usize {
+    if len <= 1 {
+        return len;
+    }
+    1 + (random_u64() % (len as u64 - 1)) as usize
+}
+
+/// Sets how far `maybe_now!` shifts clock readings for `tag` while it is enabled.
+///
+/// The offset is applied to each reading passed to the macro, so repeated readings keep
+/// advancing at the real rate, just shifted. Without a configured skew an enabled tag
+/// returns the real reading.
+pub fn configure_clock_skew(tag: &'static str, skew: ClockSkew) {
+    CLOCK_SKEWS.insert(tag, skew);
+}
+
+pub fn clear_clock_skew(tag: &str) {
+    CLOCK_SKEWS.remove(tag);
+}
+
+pub fn clock_skew(tag: &str) -> Option<ClockSkew> {
+    CLOCK_SKEWS.get(tag).map(|skew| *skew)
+}
+
+/// Marks the start of an operation whose duration `maybe_slow!` scales.
+///
+/// Call it on the same thread right before the operation; the matching `maybe_slow!`
+/// with the same tag at the end of the operation measures the time since this call as the
+/// baseline. A later `slow_begin` for the tag restarts the measurement.
+pub fn slow_begin(tag: &'static str) {
+    SLOW_STARTS.with(|starts| starts.borrow_mut().insert(tag, Instant::now()));
+}
+
+/// Ends the measurement started by [`slow_begin`] and sleeps `baseline * (factor - 1)`.
+pub fn slow_end(tag: &'static str, factor: f64) {
+    let Some(start) = SLOW_STARTS.with(|starts| starts.borrow_mut().remove(tag)) else {
+        return;
+    };
+    if factor > 1.0 {
+        let extra = start.elapsed().mul_f64(factor - 1.0);
+        record_sleep(tag, extra);
+        std::thread::sleep(extra);
+    }
+}
+
+/// Records that `maybe_fail_in_drop!` fired for `tag` on the current thread.
+pub fn record_drop_failure(tag: &'static str) {
+    DROP_FAILURES.with(|failures| failures.borrow_mut().push(tag));
+}
+
+/// Returns and clears the tags whose `maybe_fail_in_drop!` fired on the current thread,
+/// oldest first.
+pub fn take_drop_failures() -> Vec<&'static str> {
+    DROP_FAILURES.with(|failures| failures.take())
+}
+
+/// Picks how many of `len` batch results survive a partial failure: always fewer than
+/// `len` unless the batch is empty.
+pub fn partial_prefix_len(len: usize) -> usize {
+    match len {
+        0 => 0,
+        len => (random_u64() % len as u64) as usize,
+    }
+}
+
+/// Overrides the duration `maybe_sleep!` and `maybe_sleep_async!` use for `tag`.
+pub fn configure_sleep(tag: &'static str, duration: Duration) {
+    SLEEP_OVERRIDES.insert(tag, duration);
+}
+
+/// Makes the sleep macros draw each duration for `tag` from a weighted histogram.
+///
+/// Every sleep picks one bucket with probability `weight / total weight`, using the
+/// generator seeded by [`set_seed`], and sleeps that bucket's duration, so latency shapes
+/// captured from production can be replayed. Buckets with weight zero are never drawn. A
+/// duration set with [`configure_sleep`] or a curve set with [`configure_recovery`] still
+/// takes precedence, while the histogram takes precedence over the duration given to the
+/// macro. The time scale and minimum sleep apply to the drawn duration as usual.
+pub fn configure_latency_histogram(tag: &'static str, buckets: &[(Duration, u32)]) {
+    let buckets: Vec<(Duration, u32)> = buckets
+        .iter()
+        .copied()
+        .filter(|(_, weight)| *weight > 0)
+        .collect();
+    if buckets.is_empty() {
+        HISTOGRAMS.remove(tag);
+    } else {
+        HISTOGRAMS.insert(tag, buckets);
+    }
+}
+
+pub fn clear_latency_histogram(tag: &str) {
+    HISTOGRAMS.remove(tag);
+}
+
+fn sample_histogram(tag: &str) -> Option<Duration> {
+    let buckets = HISTOGRAMS.get(tag)?;
+    let total: u64 = buckets.iter().map(|(_, weight)| u64::from(*weight)).sum();
+    let mut roll = random_u64() % total;
+    for (duration, weight) in buckets.iter() {
+        match roll.checked_sub(u64::from(*weight)) {
+            Some(rest) => roll = rest,
+            None => return Some(*duration),
+        }
+    }
+    None
+}
+
+/// Makes the sleeps injected for `tag` recover linearly from `peak_ms` to nothing.
+///
+/// The delay is `peak_ms * (1 - t / recovery_duration)`, where `t` is the time since the
+/// tag was last enabled, clamped at zero once `t` reaches `recovery_duration`; a zero
+/// recovery duration sleeps nothing at all. This models a system recovering from
+/// overload: the first hits after enabling are the slowest. The curve replaces the
+/// duration given to the macro and any latency histogram, but a duration set with
+/// [`configure_sleep`] still wins. Re-enabling the tag restarts the recovery.
+pub fn configure_recovery(tag: &'static str, peak_ms: u64, recovery_duration: Duration) {
+    RECOVERIES.insert(tag, (Duration::from_millis(peak_ms), recovery_duration));
+}
+
+pub fn clear_recovery(tag: &str) {
+    RECOVERIES.remove(tag);
+}
+
+fn recovery_delay(tag: &str) -> Option<Duration> {
+    let (peak, recovery) = RECOVERIES.get(tag).map(|curve| *curve)?;
+    let elapsed = ENABLED_AT
+        .get(tag)
+        .map(|at| at.elapsed())
+        .unwrap_or_default();
+    if elapsed >= recovery {
+        return Some(Duration::ZERO);
+    }
+    let remaining = 1.0 - elapsed.as_secs_f64() / recovery.as_secs_f64();
+    Some(peak.mul_f64(remaining))
+}
+
+pub fn sleep_duration(tag: &str, default: Duration) -> Duration {
+    let duration = SLEEP_OVERRIDES
+        .get(tag)
+        .map(|d| *d)
+        .or_else(|| recovery_delay(tag))
+        .or_else(|| sample_histogram(tag))
+        .unwrap_or(default);
+    scale_duration(duration).max(min_sleep())
+}
+
+/// Makes every sleep the sleep macros inject last at least `floor`, process-wide.
+///
+/// The floor wins over both the duration given to the macro and one set with
+/// `configure_sleep`, and is applied after [`set_time_scale`], so it exaggerates tiny
+/// delays on fast machines where a race would otherwise never show up. As with the time
+/// scale, chains, default actions and `maybe_sleep_until!` are unaffected. A floor of zero,
+/// the default, turns it off.
+pub fn set_min_sleep(floor: Duration) {
+    let nanos = u64::try_from(floor.as_nanos()).unwrap_or(u64::MAX);
+    MIN_SLEEP_NANOS.store(nanos, Ordering::Relaxed);
+}
+
+/// Returns the floor set with [`set_min_sleep`].
+pub fn min_sleep() -> Duration {
+    Duration::from_nanos(MIN_SLEEP_NANOS.load(Ordering::Relaxed))
+}
+
+/// Multiplies every duration the sleep macros inject by `factor`, process-wide.
+///
+/// A factor of `0.1` runs sleeps ten times faster. The timing assertions of
+/// `with_failpoint!`, `with_failpoint_async!` and `with_failpoint_timed!` scale their
+/// expected bounds, tolerances included, by the same factor, so timing tests keep passing
+/// unchanged while running faster. Sleeps run by chains or default actions and
+/// `maybe_sleep_until!` deadlines are not scaled. Scaled durations too large to represent
+/// saturate at `Duration::MAX`.
+///
+/// # Panics
+///
+/// Panics if `factor` is negative, infinite or NaN.
+pub fn set_time_scale(factor: f64) {
+    assert!(
+        factor.is_finite() && factor >= 0.0,
+        "time scale must be finite and non-negative, got {factor}"
+    );
+    TIME_SCALE.store(factor.to_bits(), Ordering::Relaxed);
+}
+
+/// Returns the factor set with [`set_time_scale`], `1.0` by default.
+pub fn time_scale() -> f64 {
+    f64::from_bits(TIME_SCALE.load(Ordering::Relaxed))
+}
+
+/// Converts `millis` to a duration scaled by [`time_scale`].
+pub fn scaled_millis(millis: u64) -> Duration {
+    scale_duration(Duration::from_millis(millis))
+}
+
+fn scale_duration(duration: Duration) -> Duration {
+    Duration::try_from_secs_f64(duration.as_secs_f64() * time_scale()).unwrap_or(Duration::MAX)
+}
+
+pub fn sleep_internal(tag: &'static str, default: Duration) {
+    let duration = sleep_duration(tag, default);
+    record_sleep(tag, duration);
+    std::thread::sleep(duration);
+}
+
+pub fn sleep_until_internal(tag: &'static str, deadline: Instant) {
+    let duration = deadline.saturating_duration_since(Instant::now());
+    if !duration.is_zero() {
+        record_sleep(tag, duration);
+        std::thread::sleep(duration);
+    }
+}
+
+pub fn record_sleep(tag: &'static str, duration: Duration) {
+    SLEEP_STATS.entry(tag).or_default().record(duration);
+}
+
+/// Returns the count, min, max and mean of the sleeps the sleep macros injected for `tag`.
+///
+/// Sleeps run by a chain or default action are not counted. Cleared by
+/// [`clear_all_failpoints`].
+pub fn sleep_stats(tag: &str) -> SleepStats {
+    SLEEP_STATS.get(tag).map(|stats| *stats).unwrap_or_default()
+}
+
+/// What a macro should do after running a tag's configured chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainOutcome {
+    /// No chain or default action is configured; the macro performs its own action.
+    Unconfigured,
+    /// The chain ran to completion or reached [`Action::Return`] without reaching
+    /// [`Action::Error`].
+    Proceed,
+    /// The chain reached [`Action::Error`]; the macro should return its error.
+    Error,
+}
+
+/// Makes a single hit of `tag` run `actions` in order, replacing the action of the macro
+/// evaluating it.
+///
+/// Sleeps run and continue to the next step. `Action::Panic` panics immediately, and
+/// `Action::Error` and `Action::Return` stop the chain, so any later steps are skipped.
+/// Macros that cannot return an error, like `maybe_sleep!`, treat `Action::Error` as the
+/// end of the chain. Like [`enable_failpoint_with_action`], this replaces whatever action
+/// is configured for the tag.
+pub fn configure_chain(tag: &'static str, actions: &[Action]) {
+    ACTIONS.insert(tag, TagAction::Chain(actions.to_vec()));
+}
+
+/// Removes the chain configured for `tag`, leaving escalations and per-thread selectors in
+/// place.
+pub fn clear_chain(tag: &str) {
+    ACTIONS.remove_if(tag, |_, action| matches!(action, TagAction::Chain(_)));
+}
+
+#[cfg(test)]
+pub(crate) fn chain(tag: &str) -> Option<Vec<Action>> {
+    match tag_action(tag)? {
+        TagAction::Chain(actions) => Some(actions),
+        _ => None,
+    }
+}
+
+/// Makes every enabled tag without a configured action perform `action` instead of the
+/// macro's own.
+///
+/// An action configured for the tag with [`enable_failpoint_with_action`] or any of the
+/// other setters still takes precedence; the default only replaces the built-in behavior of
+/// each macro, so a default of `Action::Sleep(100ms)` makes `maybe_fail!`, `maybe_panic!`
+/// and `maybe_degrade!` alike sleep 100ms.
+pub fn set_default_action(action: Action) {
+    *DEFAULT_ACTION.lock().unwrap_or_else(|e| e.into_inner()) = Some(action);
+}
+
+/// Restores the per-macro behavior for enabled tags without a configured action.
+pub fn clear_default_action() {
+    *DEFAULT_ACTION.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Makes the action of `tag` depend on how long it has been enabled.
+///
+/// Each `(offset, action)` step applies from `offset` after the tag was enabled until the
+/// next step's offset, so `[(0s, Sleep(100ms)), (1s, Error), (2s, Panic)]` sleeps for the
+/// first second, errors during the next one and panics from then on. Steps may be given in
+/// any order. Before the earliest offset the macro performs its own action. The clock
+/// restarts whenever the tag goes from disabled to enabled. The schedule replaces whatever
+/// action is configured for the tag.
+pub fn configure_escalation(tag: &'static str, schedule: &[(Duration, Action)]) {
+    let mut schedule = schedule.to_vec();
+    schedule.sort_by_key(|(offset, _)| *offset);
+    ACTIONS.insert(tag, TagAction::Escalation(schedule));
+}
+
+/// Removes the escalation configured for `tag`, leaving other kinds of action in place.
+pub fn clear_escalation(tag: &str) {
+    ACTIONS.remove_if(tag, |_, action| matches!(action, TagAction::Escalation(_)));
+}
+
+fn escalated_action(tag: &str, schedule: &[(Duration, Action)]) -> Option<Action> {
+    let elapsed = ENABLED_AT
+        .get(tag)
+        .map(|at| at.elapsed())
+        .unwrap_or_default();
+    schedule
+        .iter()
+        .rev()
+        .find(|(offset, _)| *offset <= elapsed)
+        .map(|(_, action)| *action)
+}
+
+/// Lets each thread evaluating `tag` get its own action, chosen by calling `select` with
+/// the evaluating thread's [`ThreadId`].
+///
+/// `select` runs on every hit, on the thread that hit the failpoint, so threads are told
+/// apart by `std::thread::current().id()`; async tasks get the id of whichever worker
+/// thread polls them. The selector replaces whatever action is configured for the tag, and
+/// takes precedence over [`set_default_action`].
+pub fn configure_per_thread(tag: &'static str, select: Box<PerThreadAction>) {
+    ACTIONS.insert(tag, TagAction::PerThread(Arc::from(select)));
+}
+
+/// Removes the per-thread selector configured for `tag`, leaving other kinds of action in
+/// place.
+pub fn clear_per_thread(tag: &str) {
+    ACTIONS.remove_if(tag, |_, action| matches!(action, TagAction::PerThread(_)));
+}
+
+fn effective_chain(tag: &str) -> Option<Vec<Action>> {
+    let action = match tag_action(tag) {
+        Some(TagAction::Chain(actions)) => return Some(actions),
+        Some(TagAction::Escalation(schedule)) => escalated_action(tag, &schedule),
+        Some(TagAction::PerThread(select)) => Some(select(std::thread::current().id())),
+        None => None,
+    };
+    action
+        .or_else(|| *DEFAULT_ACTION.lock().unwrap_or_else(|e| e.into_inner()))
+        .map(|action| vec![action])
+}
+
+pub fn execute_chain(tag: &str) -> ChainOutcome {
+    let Some(actions) = effective_chain(tag) else {
+        return ChainOutcome::Unconfigured;
+    };
+    for action in actions {
+        match action {
+            Action::Error => return ChainOutcome::Error,
+            Action::Panic => panic!("{}", tag),
+            Action::Sleep(duration) => std::thread::sleep(duration),
+            Action::Return => break,
+        }
+    }
+    ChainOutcome::Proceed
+}
+
+pub async fn execute_chain_async(tag: &str) -> ChainOutcome {
+    let Some(actions) = effective_chain(tag) else {
+        return ChainOutcome::Unconfigured;
+    };
+    for action in actions {
+        match action {
+            Action::Error => return ChainOutcome::Error,
+            Action::Panic => panic!("{}", tag),
+            Action::Sleep(duration) => sleep_async_internal(duration).await,
+            Action::Return => break,
+        }
+    }
+    ChainOutcome::Proceed
+}
+
+/// Applies `predicate` to `err`; gives closures passed to `error_matching` their argument
+/// type.
+pub fn error_matches<E>(err: &E, predicate: impl FnOnce(&E) -> bool) -> bool {
+    predicate(err)
+}
+
+/// Formats the context `with_failpoint!` appends to a failed assertion for `tag`: its hit
+/// count, configured action, and last five entries in the injection log.
+pub fn failure_context(tag: &str) -> String {
+    let action = match effective_chain(tag) {
+        Some(actions) => format!("{actions:?}"),
+        None => "macro default".to_string(),
+    };
+    let mut context = format!(
+        "\nfailpoint '{tag}' context:\n  hits: {}\n  action: {action}\n  recent injections:",
+        failpoint_hits(tag)
+    );
+    let log = crate::injection_log();
+    let recent: Vec<_> = log.iter().filter(|record| record.tag == tag).collect();
+    if recent.is_empty() {
+        context.push_str(" none");
+    }
+    for record in &recent[recent.len().saturating_sub(5)..] {
+        context.push_str(&format!("\n    {} at {}", record.action, record.location));
+    }
+    context
+}
+
+/// Extracts the message of a panic payload, for `&str` and `String` payloads.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
+
+/// Polls `future` to completion, returning `Err` with the payload if any poll panics.
+pub async fn catch_unwind_async<F: Future>(future: F) -> Result<F::Output, Box<dyn Any + Send>> {
+    let mut future = std::pin::pin!(future);
+    std::future::poll_fn(|cx| {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    })
+    .await
+}
+
+/// Returns a uniformly random duration in `[min_ms, max_ms]` milliseconds.
+pub fn random_duration(min_ms: u64, max_ms: u64) -> Duration {
+    let (low, high) = (min_ms.min(max_ms), min_ms.max(max_ms));
+    let span = high - low;
+    let offset = if span == u64::MAX {
+        random_u64()
+    } else {
+        random_u64() % (span + 1)
+    };
+    Duration::from_millis(low + offset)
+}
+
+/// Sleeps asynchronously using Tokio's timer with the `tokio` feature, so paused time
+/// advances virtually, and `futures-timer` otherwise.
 pub async fn sleep_async_internal(millis: std::time::Duration) {
+    #[cfg(feature = "tokio")]
+    tokio::time::sleep(millis).await;
+    #[cfg(not(feature = "tokio"))]
     futures_timer::Delay::new(millis).await;
 }
+
+/// Wakes every `maybe_block!` and `maybe_block_async!` currently waiting on `tag`.
+///
+/// Only callers already blocked are released; later evaluations block again.
+pub fn release_failpoint(tag: &str) {
+    release_failpoints(&[tag]);
+}
+
+fn release_failpoints(tags: &[&str]) {
+    let (lock, condvar) = &*BLOCKS;
+    let mut state = lock.lock().unwrap_or_else(|e| e.into_inner());
+    #[cfg(test)]
+    RELEASE_LOCKS.with(|locks| locks.set(locks.get() + 1));
+    for &tag in tags {
+        if let Some(releases) = state.releases.get_mut(tag) {
+            *releases += 1;
+        }
+        for (_, waker) in state.wakers.remove(tag).into_iter().flatten() {
+            waker.wake();
+        }
+    }
+    condvar.notify_all();
+}
+
+pub fn block_internal(tag: &'static str) {
+    let (lock, condvar) = &*BLOCKS;
+    let mut state = lock.lock().unwrap_or_else(|e| e.into_inner());
+    let start = state.join(tag);
+    while state.releases[tag] == start {
+        state = condvar.wait(state).unwrap_or_else(|e| e.into_inner());
+    }
+    state.leave(tag, None);
+}
+
+/// Makes the next thread that evaluates `tag_a` wait until some thread evaluates `tag_b`,
+/// forcing `tag_b` to be reached first.
+///
+/// Evaluations count whether or not the tags are enabled, so the barrier doesn't change
+/// what either failpoint does. If `tag_b` was already evaluated after this call, `tag_a`
+/// passes straight through. The barrier is used up once `tag_a` gets past it.
+///
+/// Waiting blocks the thread, so a thread (or single-threaded async runtime) that has to
+/// reach `tag_b` itself will deadlock. After `timeout` the waiter gives up and carries on
+/// as if the rendezvous had happened.
+pub fn failpoint_barrier(tag_a: &'static str, tag_b: &'static str, timeout: Duration) {
+    let mut barriers = BARRIERS.0.lock().unwrap_or_else(|e| e.into_inner());
+    barriers.retain(|barrier| barrier.waiter != tag_a);
+    barriers.push(Rendezvous {
+        waiter: tag_a,
+        signal: tag_b,
+        timeout,
+        arrived: false,
+    });
+    BARRIERS_ACTIVE.store(true, Ordering::Relaxed);
+}
+
+fn rendezvous(tag: &'static str) {
+    let (lock, condvar) = &*BARRIERS;
+    let mut barriers = lock.lock().unwrap_or_else(|e| e.into_inner());
+    let mut signalled = false;
+    for barrier in barriers.iter_mut().filter(|b| b.signal == tag) {
+        barrier.arrived = true;
+        signalled = true;
+    }
+    if signalled {
+        condvar.notify_all();
+    }
+
+    let Some(timeout) = barriers.iter().find(|b| b.waiter == tag).map(|b| b.timeout) else {
+        return;
+    };
+    let arrived = |barriers: &mut Vec<Rendezvous>| {
+        barriers
+            .iter()
+            .find(|b| b.waiter == tag)
+            .is_none_or(|b| b.arrived)
+    };
+    let (mut barriers, _) = condvar
+        .wait_timeout_while(barriers, timeout, |barriers| !arrived(barriers))
+        .unwrap_or_else(|e| e.into_inner());
+    barriers.retain(|b| b.waiter != tag);
+    BARRIERS_ACTIVE.store(!barriers.is_empty(), Ordering::Relaxed);
+}
+
+/// Future returned by [`block_async_internal`]; resolves on release or timeout.
+///
+/// Its waker is dropped from the shared state as soon as it resolves or is dropped, so
+/// timed-out waits leave nothing behind.
+pub struct BlockFuture {
+    tag: &'static str,
+    id: u64,
+    /// The release count to wait past, set on the first poll and cleared once done.
+    start: Option<u64>,
+    done: bool,
+    timeout: Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+impl BlockFuture {
+    fn finish(&mut self, state: &mut BlockState) {
+        if self.start.take().is_some() {
+            state.leave(self.tag, Some(self.id));
+        }
+        self.done = true;
+    }
+}
+
+impl Future for BlockFuture {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.done {
+            return Poll::Ready(());
+        }
+        let mut state = BLOCKS.0.lock().unwrap_or_else(|e| e.into_inner());
+        let tag = self.tag;
+        let start = *self.start.get_or_insert_with(|| state.join(tag));
+        if state.releases[self.tag] != start || self.timeout.as_mut().poll(cx).is_ready() {
+            self.finish(&mut state);
+            return Poll::Ready(());
+        }
+        let id = self.id;
+        let wakers = state.wakers.entry(tag).or_default();
+        match wakers.iter_mut().find(|(waiter, _)| *waiter == id) {
+            Some((_, waker)) => waker.clone_from(cx.waker()),
+            None => wakers.push((id, cx.waker().clone())),
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for BlockFuture {
+    fn drop(&mut self) {
+        if self.start.is_some() {
+            let mut state = BLOCKS.0.lock().unwrap_or_else(|e| e.into_inner());
+            self.finish(&mut state);
+        }
+    }
+}
+
+/// Returns whether anything about a wait on `tag` is still recorded.
+#[cfg(test)]
+pub(crate) fn has_block_state(tag: &str) -> bool {
+    let state = BLOCKS.0.lock().unwrap_or_else(|e| e.into_inner());
+    state.releases.contains_key(tag)
+        || state.waiters.contains_key(tag)
+        || state.wakers.contains_key(tag)
+}
+
+pub fn block_async_internal(tag: &'static str, timeout: Duration) -> BlockFuture {
+    BlockFuture {
+        tag,
+        id: NEXT_BLOCK_ID.fetch_add(1, Ordering::Relaxed),
+        start: None,
+        done: false,
+        timeout: Box::pin(sleep_async_internal(timeout)),
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+/// A single step a failpoint performs when it fires.
+///
+/// Each tag has at most one configured action. The canonical way to set it is
+/// [`enable_failpoint_with_action`](crate::enable_failpoint_with_action);
+/// [`configure_chain`](crate::configure_chain) runs several steps in sequence,
+/// [`configure_escalation`](crate::configure_escalation) changes the step over time and
+/// [`configure_per_thread`](crate::configure_per_thread) picks one per thread, each
+/// replacing whatever was configured before. [`set_default_action`](crate::set_default_action)
+/// applies an action to every enabled tag without one of its own.
+///
+/// Without a configured action each macro does its own thing: `maybe_fail!` returns its
+/// error, `maybe_panic!` panics and `maybe_sleep!` sleeps. With one, the steps run in its
+/// place:
+/// - Macros that return an error, such as `maybe_fail!`, `maybe_fail_read!` or
+///   `maybe_partial_fail!`, and the `fail_iter`, `fail_stream` and `ChaosLayer::error`
+///   adapters, return it when the steps reach `Error` and otherwise carry on as if the
+///   failpoint were disabled.
+/// - Every other macro, from `maybe_sleep!` and `maybe_panic!` to `maybe_degrade!`,
+///   `maybe_reorder!` or `maybe_block!`, and the `fail_sink` and `ChaosLayer::latency`
+///   adapters, run the steps instead of their own injection; `Error` and `Return` just end
+///   them.
+///
+/// `maybe_fail_in_drop!` is the exception: it runs inside `Drop`, where panicking can abort
+/// the process, so it ignores configured actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Makes the failpoint return its error. Only `maybe_fail!`-style macros can return
+    /// an error; other macros stop at this step.
+    Error,
+    /// Panics with the failpoint's tag as the message.
+    Panic,
+    /// Sleeps for the given duration, then continues with the next step.
+    Sleep(Duration),
+    /// Stops without doing anything further: the macro neither fails, panics nor sleeps,
+    /// and the code after it runs as if the failpoint were disabled. The hit is still
+    /// counted and logged.
+    Return,
+}
+
+/// Which direction of I/O a failpoint enabled with
+/// [`enable_failpoint_direction`](crate::enable_failpoint_direction) impairs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Only `maybe_fail_read!` fires.
+    Read,
+    /// Only `maybe_fail_write!` fires.
+    Write,
+    /// Both fire, as with a plain `enable_failpoint`.
+    Both,
+}
+
+impl Direction {
+    pub fn allows(self, other: Direction) -> bool {
+        self == Direction::Both || other == Direction::Both || self == other
+    }
+}
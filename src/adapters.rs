@@ -0,0 +1,362 @@
+#[cfg(any(feature = "stream", feature = "sink", feature = "tower"))]
+use std::pin::Pin;
+#[cfg(any(feature = "stream", feature = "sink", feature = "tower"))]
+use std::task::{Context, Poll};
+
+use crate::__failpoint_internal::{ChainOutcome, execute_chain};
+
+#[track_caller]
+fn fires(tag: &'static str, action: &'static str) -> bool {
+    cfg!(feature = "chaos") && crate::__failpoint_internal::should_fire(tag, action)
+}
+
+/// Like `fires`, for adapters that inject an error: also runs the tag's configured action,
+/// which must reach `Action::Error` for the error to be injected.
+#[track_caller]
+fn fails(tag: &'static str, action: &'static str) -> bool {
+    fires(tag, action) && execute_chain(tag) != ChainOutcome::Proceed
+}
+
+/// Iterator returned by [`fail_iter`].
+pub struct FailIter<I, F> {
+    inner: I,
+    tag: &'static str,
+    make_err: F,
+}
+
+/// Wraps an iterator of `Result` items so that each item is replaced by `Err(make_err())`
+/// whenever `tag` fires.
+///
+/// The inner item is still consumed and then dropped, so the wrapped iterator ends when the
+/// inner one does. When the failpoint is disabled items are forwarded unchanged.
+///
+/// # Example
+/// ```rust
+/// let items = [Ok::<u32, String>(1), Ok(2)];
+/// let items: Vec<_> = chaos_rs::fail_iter(items, "iter_fail", || "injected".into()).collect();
+/// assert_eq!(items, [Ok(1), Ok(2)]);
+/// ```
+pub fn fail_iter<I, T, E, F>(iter: I, tag: &'static str, make_err: F) -> FailIter<I::IntoIter, F>
+where
+    I: IntoIterator<Item = Result<T, E>>,
+    F: FnMut() -> E,
+{
+    FailIter {
+        inner: iter.into_iter(),
+        tag,
+        make_err,
+    }
+}
+
+impl<I, T, E, F> Iterator for FailIter<I, F>
+where
+    I: Iterator<Item = Result<T, E>>,
+    F: FnMut() -> E,
+{
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next()?;
+        if fails(self.tag, "fail") {
+            return Some(Err((self.make_err)()));
+        }
+        Some(item)
+    }
+}
+
+/// Stream returned by [`fail_stream`].
+#[cfg(feature = "stream")]
+pub struct FailStream<S, F> {
+    inner: Pin<Box<S>>,
+    tag: &'static str,
+    make_err: F,
+}
+
+/// Wraps a stream of `Result` items so that each item is replaced by `Err(make_err())`
+/// whenever `tag` fires.
+///
+/// The inner item is still polled and then dropped, so the wrapped stream ends when the
+/// inner one does. When the failpoint is disabled items are forwarded unchanged. An action
+/// configured for the tag runs inside `poll_next`, so its sleeps block the polling thread.
+///
+/// # Example
+/// ```rust
+/// use futures::StreamExt;
+///
+/// # futures::executor::block_on(async {
+/// let items = futures::stream::iter([Ok::<u32, String>(1), Ok(2)]);
+/// let items: Vec<_> = chaos_rs::fail_stream(items, "stream_fail", || "injected".to_string())
+///     .collect()
+///     .await;
+/// assert_eq!(items, [Ok(1), Ok(2)]);
+/// # });
+/// ```
+#[cfg(feature = "stream")]
+pub fn fail_stream<S, T, E, F>(stream: S, tag: &'static str, make_err: F) -> FailStream<S, F>
+where
+    S: futures_core::Stream<Item = Result<T, E>>,
+    F: FnMut() -> E,
+{
+    FailStream {
+        inner: Box::pin(stream),
+        tag,
+        make_err,
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<S, T, E, F> futures_core::Stream for FailStream<S, F>
+where
+    S: futures_core::Stream<Item = Result<T, E>>,
+    F: FnMut() -> E + Unpin,
+{
+    type Item = Result<T, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.inner.as_mut().poll_next(cx) {
+            Poll::Ready(Some(item)) if fails(self.tag, "fail") => {
+                drop(item);
+                Poll::Ready(Some(Err((self.make_err)())))
+            }
+            other => other,
+        }
+    }
+}
+
+/// Sink returned by [`fail_sink`].
+#[cfg(feature = "sink")]
+pub struct FailSink<S> {
+    inner: Pin<Box<S>>,
+    tag: &'static str,
+    drop_prob: f64,
+}
+
+/// Wraps a sink so that, while `tag` is enabled, each item sent has a `drop_prob` chance of
+/// being silently dropped instead of forwarded, simulating a lossy channel.
+///
+/// Dropped items are reported as sent successfully, and only they count as hits. The roll
+/// uses the generator seeded by `set_seed`. When the failpoint is disabled every item is forwarded unchanged, and
+/// readiness, flushing and closing are always delegated to the inner sink. An action
+/// configured for the tag runs in place of the drop and the item is forwarded.
+///
+/// # Example
+/// ```rust
+/// use futures::SinkExt;
+///
+/// # futures::executor::block_on(async {
+/// let (tx, rx) = futures::channel::mpsc::unbounded::<u32>();
+/// let mut lossy = chaos_rs::fail_sink(tx, "lossy_channel", 0.25);
+/// lossy.send(1).await.unwrap();
+/// # });
+/// ```
+#[cfg(feature = "sink")]
+pub fn fail_sink<S, T>(sink: S, tag: &'static str, drop_prob: f64) -> FailSink<S>
+where
+    S: futures_sink::Sink<T>,
+{
+    FailSink {
+        inner: Box::pin(sink),
+        tag,
+        drop_prob: drop_prob.clamp(0.0, 1.0),
+    }
+}
+
+#[cfg(feature = "sink")]
+impl<S, T> futures_sink::Sink<T> for FailSink<S>
+where
+    S: futures_sink::Sink<T>,
+{
+    type Error = S::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.as_mut().poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), S::Error> {
+        use crate::__failpoint_internal::{
+            is_failpoint_enabled, random_f64, register_tag, should_fire_registered,
+        };
+
+        // Roll before evaluating the failpoint so only items actually dropped count as hits.
+        let tag = self.tag;
+        if cfg!(feature = "chaos") {
+            register_tag(tag);
+            if is_failpoint_enabled(tag)
+                && random_f64() < self.drop_prob
+                && should_fire_registered(tag, "drop")
+                && execute_chain(tag) == ChainOutcome::Unconfigured
+            {
+                drop(item);
+                return Ok(());
+            }
+        }
+        self.inner.as_mut().start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.as_mut().poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.as_mut().poll_close(cx)
+    }
+}
+
+#[cfg(feature = "tower")]
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[cfg(feature = "tower")]
+type BoxFuture<T> = Pin<Box<dyn std::future::Future<Output = Result<T, BoxError>> + Send>>;
+
+/// The error a [`ChaosService`] returns for a request it short-circuits.
+#[cfg(feature = "tower")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChaosError {
+    tag: &'static str,
+}
+
+#[cfg(feature = "tower")]
+impl ChaosError {
+    /// The failpoint that rejected the request.
+    pub fn tag(&self) -> &'static str {
+        self.tag
+    }
+}
+
+#[cfg(feature = "tower")]
+impl std::fmt::Display for ChaosError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request rejected by failpoint '{}'", self.tag)
+    }
+}
+
+#[cfg(feature = "tower")]
+impl std::error::Error for ChaosError {}
+
+#[cfg(feature = "tower")]
+#[derive(Debug, Clone, Copy)]
+enum Injection {
+    Error,
+    Latency(std::time::Duration),
+}
+
+/// A tower `Layer` that wraps services in a [`ChaosService`] driven by one failpoint.
+///
+/// Build it with [`error`](Self::error) to reject requests with a [`ChaosError`] while the
+/// tag is enabled, or with [`latency`](Self::latency) to delay them before they reach the
+/// inner service. The wrapped service's error type becomes a boxed error, so inner errors
+/// need to convert into `Box<dyn Error + Send + Sync>`.
+///
+/// # Example
+/// ```rust
+/// use chaos_rs::ChaosLayer;
+/// use std::time::Duration;
+///
+/// let reject = ChaosLayer::error("upstream_down");
+/// let slow = ChaosLayer::latency("upstream_slow", Duration::from_millis(200));
+/// # let _ = (reject, slow);
+/// ```
+#[cfg(feature = "tower")]
+#[derive(Debug, Clone, Copy)]
+pub struct ChaosLayer {
+    tag: &'static str,
+    injection: Injection,
+}
+
+#[cfg(feature = "tower")]
+impl ChaosLayer {
+    /// Rejects each request with a [`ChaosError`] while `tag` is enabled, without calling
+    /// the inner service.
+    pub fn error(tag: &'static str) -> Self {
+        Self {
+            tag,
+            injection: Injection::Error,
+        }
+    }
+
+    /// Delays each request by `delay` while `tag` is enabled, then calls the inner service.
+    ///
+    /// A duration set with `configure_sleep` takes precedence over `delay`, and the delay is
+    /// counted in [`sleep_stats`](crate::sleep_stats).
+    pub fn latency(tag: &'static str, delay: std::time::Duration) -> Self {
+        Self {
+            tag,
+            injection: Injection::Latency(delay),
+        }
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<S> tower_layer::Layer<S> for ChaosLayer {
+    type Service = ChaosService<S>;
+
+    fn layer(&self, inner: S) -> ChaosService<S> {
+        ChaosService {
+            inner,
+            tag: self.tag,
+            injection: self.injection,
+        }
+    }
+}
+
+/// Service produced by [`ChaosLayer`].
+///
+/// Whether to inject is decided when the request is called, so enabling or disabling the
+/// tag affects requests made afterwards. An action configured for the tag runs inside the
+/// response future, with sleeps awaited: `Action::Error` rejects the request with a
+/// [`ChaosError`], and any other outcome forwards it. The inner service is cloned to run
+/// requests whose injection is deferred, following the usual tower pattern.
+#[cfg(feature = "tower")]
+#[derive(Debug, Clone)]
+pub struct ChaosService<S> {
+    inner: S,
+    tag: &'static str,
+    injection: Injection,
+}
+
+#[cfg(feature = "tower")]
+impl<S, Request> tower_service::Service<Request> for ChaosService<S>
+where
+    S: tower_service::Service<Request> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<S::Response>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let tag = self.tag;
+        let injection = self.injection;
+        let action = match injection {
+            Injection::Error => "reject request",
+            Injection::Latency(_) => "delay request",
+        };
+        if !fires(tag, action) {
+            let future = self.inner.call(request);
+            return Box::pin(async move { future.await.map_err(Into::into) });
+        }
+        let ready = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, ready);
+        Box::pin(async move {
+            match crate::__failpoint_internal::execute_chain_async(tag).await {
+                ChainOutcome::Error => return Err(ChaosError { tag }.into()),
+                ChainOutcome::Proceed => {}
+                ChainOutcome::Unconfigured => match injection {
+                    Injection::Error => return Err(ChaosError { tag }.into()),
+                    Injection::Latency(delay) => {
+                        let delay = crate::__failpoint_internal::sleep_duration(tag, delay);
+                        crate::__failpoint_internal::record_sleep(tag, delay);
+                        crate::__failpoint_internal::sleep_async_internal(delay).await;
+                    }
+                },
+            }
+            inner.call(request).await.map_err(Into::into)
+        })
+    }
+}
@@ -0,0 +1,29 @@
+use std::fmt;
+
+/// The error returned by `maybe_alloc_fail!` to simulate an allocation failure.
+///
+/// Nothing is actually allocated or exhausted; the failure is only reported, so the
+/// caller's out-of-memory handling can run without the process aborting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError {
+    tag: &'static str,
+}
+
+impl AllocError {
+    pub fn new(tag: &'static str) -> Self {
+        Self { tag }
+    }
+
+    /// The failpoint that injected this failure.
+    pub fn tag(&self) -> &'static str {
+        self.tag
+    }
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "injected allocation failure at '{}'", self.tag)
+    }
+}
+
+impl std::error::Error for AllocError {}
@@ -0,0 +1,35 @@
+use std::fmt;
+
+/// The error returned by `maybe_fail_code!`, carrying a numeric error code.
+///
+/// Lets tests assert on a protocol-style code instead of matching on a message. It
+/// displays as `injected error <code> at '<tag>'`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodedError {
+    code: u32,
+    tag: &'static str,
+}
+
+impl CodedError {
+    pub fn new(tag: &'static str, code: u32) -> Self {
+        Self { code, tag }
+    }
+
+    /// The code the failpoint was configured to return.
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
+    /// The failpoint that injected this failure.
+    pub fn tag(&self) -> &'static str {
+        self.tag
+    }
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "injected error {} at '{}'", self.code, self.tag)
+    }
+}
+
+impl std::error::Error for CodedError {}
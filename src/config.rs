@@ -0,0 +1,160 @@
+use crate::__failpoint_internal::{
+    clear_probability, configure_probability, configure_sleep, enable_failpoint,
+    enable_failpoint_with_action, intern_tag,
+};
+use crate::Action;
+use std::fmt;
+use std::time::Duration;
+
+/// The action named in a [`FailpointConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ConfigAction {
+    Error,
+    Panic,
+    Sleep,
+}
+
+/// Declarative configuration for one failpoint.
+///
+/// With the `serde` feature this (de)serializes as
+/// `{"action": "sleep", "ms": 200, "prob": 0.5}`, where `ms` is required for `sleep`
+/// and `prob` is optional.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FailpointConfig {
+    pub action: ConfigAction,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub ms: Option<u64>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub prob: Option<f64>,
+}
+
+impl FailpointConfig {
+    pub fn to_action(&self) -> Result<Action, ConfigError> {
+        match self.action {
+            ConfigAction::Error => Ok(Action::Error),
+            ConfigAction::Panic => Ok(Action::Panic),
+            ConfigAction::Sleep => self
+                .ms
+                .map(|ms| Action::Sleep(Duration::from_millis(ms)))
+                .ok_or_else(|| ConfigError::new("sleep action requires \"ms\"")),
+        }
+    }
+
+    /// Configures `tag` with this action and probability, then enables it.
+    #[track_caller]
+    pub fn apply(&self, tag: &'static str) -> Result<(), ConfigError> {
+        let action = self.to_action()?;
+        match self.prob {
+            Some(prob) => configure_probability(tag, prob),
+            None => clear_probability(tag),
+        }
+        enable_failpoint_with_action(tag, action);
+        Ok(())
+    }
+}
+
+/// Error returned when failpoint configuration cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    message: String,
+}
+
+impl ConfigError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Configures failpoints from command-line flags and returns the arguments it did not consume.
+///
+/// Recognized flags:
+/// - `--chaos-enable=<tag>` enables `tag`.
+/// - `--chaos-sleep=<tag>:<millis>` enables `tag` and makes its sleeps last `millis`.
+///
+/// All other arguments are returned unchanged and in order. Tags are leaked to obtain the
+/// `'static` lifetime failpoints require, once per distinct tag, so repeated calls with the
+/// same tags don't leak more.
+///
+/// # Example
+/// ```rust
+/// let args = ["app", "--chaos-enable=db_error", "--verbose"].map(String::from);
+/// let rest = chaos_rs::init_from_args(args.into_iter()).unwrap();
+/// assert_eq!(rest, ["app", "--verbose"]);
+/// ```
+#[track_caller]
+pub fn init_from_args(args: impl Iterator<Item = String>) -> Result<Vec<String>, ConfigError> {
+    let mut rest = Vec::new();
+    for arg in args {
+        if let Some(tag) = arg.strip_prefix("--chaos-enable=") {
+            enable_failpoint(leak_tag(tag)?);
+        } else if let Some(spec) = arg.strip_prefix("--chaos-sleep=") {
+            let (tag, millis) = spec
+                .rsplit_once(':')
+                .ok_or_else(|| ConfigError::new(format!("expected <tag>:<millis> in '{}'", arg)))?;
+            let millis: u64 = millis
+                .parse()
+                .map_err(|_| ConfigError::new(format!("invalid millis in '{}'", arg)))?;
+            let tag = leak_tag(tag)?;
+            configure_sleep(tag, Duration::from_millis(millis));
+            enable_failpoint(tag);
+        } else {
+            rest.push(arg);
+        }
+    }
+    Ok(rest)
+}
+
+fn leak_tag(tag: &str) -> Result<&'static str, ConfigError> {
+    if tag.is_empty() {
+        return Err(ConfigError::new("failpoint tag must not be empty"));
+    }
+    Ok(intern_tag(tag))
+}
+
+/// Configures failpoints from a JSON document mapping tags to [`FailpointConfig`]s.
+///
+/// ```json
+/// {"db_error": {"action": "error"}, "slow_io": {"action": "sleep", "ms": 200, "prob": 0.5}}
+/// ```
+///
+/// The whole document is validated before anything is applied, so a malformed entry leaves
+/// the configuration unchanged. Tags are leaked like in [`init_from_args`], so documents can
+/// be applied repeatedly, as a remote control would.
+#[cfg(feature = "json")]
+#[track_caller]
+pub fn configure_json(json: &str) -> Result<(), ConfigError> {
+    let configs: std::collections::BTreeMap<String, FailpointConfig> =
+        serde_json::from_str(json)
+            .map_err(|e| ConfigError::new(format!("invalid failpoint JSON: {}", e)))?;
+    for (tag, config) in &configs {
+        if tag.is_empty() {
+            return Err(ConfigError::new("failpoint tag must not be empty"));
+        }
+        config
+            .to_action()
+            .map_err(|e| ConfigError::new(format!("failpoint '{}': {}", tag, e)))?;
+    }
+    for (tag, config) in &configs {
+        config.apply(leak_tag(tag)?)?;
+    }
+    Ok(())
+}
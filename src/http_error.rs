@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// The error returned by `maybe_http_fail!`, standing in for an HTTP error response.
+///
+/// Carries the status the failpoint was configured with, so client code can run the same
+/// retry and backoff logic it applies to real error responses. [`response`](Self::response)
+/// builds an empty `http::Response` with that status for code that works on responses
+/// rather than errors. It displays as `injected HTTP <status> at '<tag>'`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpError {
+    status: http::StatusCode,
+    tag: &'static str,
+}
+
+impl HttpError {
+    pub fn new(tag: &'static str, status: http::StatusCode) -> Self {
+        Self { status, tag }
+    }
+
+    /// The status the failpoint was configured to return.
+    pub fn status(&self) -> http::StatusCode {
+        self.status
+    }
+
+    /// The failpoint that injected this failure.
+    pub fn tag(&self) -> &'static str {
+        self.tag
+    }
+
+    /// Builds a synthetic response with [`status`](Self::status) and an empty body.
+    pub fn response(&self) -> http::Response<()> {
+        let mut response = http::Response::new(());
+        *response.status_mut() = self.status;
+        response
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "injected HTTP {} at '{}'", self.status, self.tag)
+    }
+}
+
+impl std::error::Error for HttpError {}
@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+use std::panic::Location;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// One injection recorded in the [`injection_log`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InjectionRecord {
+    /// The tag that fired.
+    pub tag: &'static str,
+    /// What the evaluating macro does when it fires, e.g. `"fail"` or `"sleep"`.
+    pub action: &'static str,
+    /// Where the failpoint was evaluated: the macro invocation in user code.
+    pub location: &'static Location<'static>,
+}
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_CAPACITY);
+
+static LOG: Mutex<VecDeque<InjectionRecord>> = Mutex::new(VecDeque::new());
+
+/// Returns the most recent injections across all threads, oldest first.
+///
+/// Every fire of a failpoint is appended, replayed fires included. The log is a ring buffer
+/// holding the last 1024 records by default; older ones are dropped as new ones arrive, so
+/// it never grows without bound. Change the size with [`set_injection_log_capacity`].
+pub fn injection_log() -> Vec<InjectionRecord> {
+    LOG.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .copied()
+        .collect()
+}
+
+/// Forgets every record in the [`injection_log`].
+pub fn clear_injection_log() {
+    LOG.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Sets how many records the [`injection_log`] keeps, dropping the oldest ones if it
+/// already holds more. A capacity of zero turns logging off.
+pub fn set_injection_log_capacity(capacity: usize) {
+    CAPACITY.store(capacity, Ordering::Relaxed);
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    let excess = log.len().saturating_sub(capacity);
+    log.drain(..excess);
+}
+
+pub(crate) fn record_injection(
+    tag: &'static str,
+    action: &'static str,
+    location: &'static Location<'static>,
+) {
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    while log.len() >= capacity {
+        log.pop_front();
+    }
+    log.push_back(InjectionRecord {
+        tag,
+        action,
+        location,
+    });
+}
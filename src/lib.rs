@@ -9,9 +9,17 @@
 //! - **Fail injection**: Return errors from tagged failpoints (`maybe_fail!`).
 //! - **Panic simulation**: Trigger panics when failpoints are enabled (`maybe_panic!`).
 //! - **Sleep injection**: Add artificial delays for timing tests (`maybe_sleep!`) and async with
-//! (`maybe_sleep_async!`).
+//!   (`maybe_sleep_async!`).
 //! - **Assertion helpers**: Verify that failpoints behave as expected (`with_failpoint!`) or
-//! (`with_failpoint_async!`) for async.
+//!   (`with_failpoint_async!`) for async.
+//!
+//! ## Failpoint modes
+//! An enabled failpoint fires according to one of these modes:
+//! - **Sustained** (`enable_failpoint_sustained`, or plain `enable_failpoint`): fires on
+//!   every evaluation until the tag is disabled.
+//! - **Cooldown** (`configure_cooldown`): fires once, then stays quiet for a period.
+//! - **Single fire per group** (`set_single_fire_per_group`): only one randomly chosen
+//!   member of a group fires within a request.
 //!
 //! ## Example
 //! ```rust
@@ -22,7 +30,73 @@
 //! ```
 
 pub mod __failpoint_internal;
+mod action;
+mod adapters;
+mod alloc;
+mod coded;
+mod config;
+#[cfg(feature = "http")]
+mod http_error;
+mod injections;
 mod macros;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod scenario;
+mod scope;
+mod session;
+mod snapshot;
+mod timing;
+
+pub use __failpoint_internal::{
+    activity_report, begin_request, clear_action, clear_all_failpoints, clear_chain,
+    clear_clock_skew, clear_cooldown, clear_decay, clear_default_action, clear_escalation,
+    clear_global_injection_budget, clear_latency_histogram, clear_per_thread, clear_probability,
+    clear_recovery, configure_chain, configure_clock_skew, configure_cooldown, configure_decay,
+    configure_escalation, configure_latency_histogram, configure_per_thread, configure_probability,
+    configure_recovery, configure_sleep, configure_write_fraction, disable_failpoints,
+    enable_failpoint_direction, enable_failpoint_if_debug, enable_failpoint_limited,
+    enable_failpoint_on_fraction, enable_failpoint_sustained, enable_failpoint_with_action,
+    enable_failpoints, enabled_failpoint_count, enabled_failpoints, end_request, failpoint_barrier,
+    failpoint_hits, failpoint_origin, generation, known_failpoints, min_sleep, on_state_change,
+    pop_suppress, push_suppress, register_child, release_failpoint, reset_failpoint_count,
+    reset_failpoint_count_prefix, resume_chaos_on_current_thread, set_default_action, set_dry_run,
+    set_failpoint_group, set_generation, set_global_injection_budget, set_interactive,
+    set_min_sleep, set_process_seed, set_runtime_chaos, set_seed, set_single_fire_per_group,
+    set_time_scale, sleep_stats, slow_begin, step, suppress_chaos_on_current_thread,
+    take_drop_failures, time_scale,
+};
+pub use action::{Action, Direction};
+#[cfg(feature = "tower")]
+pub use adapters::{ChaosError, ChaosLayer, ChaosService};
+pub use adapters::{FailIter, fail_iter};
+#[cfg(feature = "sink")]
+pub use adapters::{FailSink, fail_sink};
+#[cfg(feature = "stream")]
+pub use adapters::{FailStream, fail_stream};
+pub use alloc::AllocError;
+#[cfg(feature = "macros")]
+pub use chaos_rs_macros::{chaos_scatter, chaos_test};
+pub use coded::CodedError;
+#[cfg(feature = "json")]
+pub use config::configure_json;
+pub use config::{ConfigAction, ConfigError, FailpointConfig, init_from_args};
+#[cfg(feature = "http")]
+pub use http_error::HttpError;
+pub use injections::{
+    InjectionRecord, clear_injection_log, injection_log, set_injection_log_capacity,
+};
+#[cfg(feature = "metrics")]
+pub use metrics::encode_metrics;
+pub use scenario::{
+    ExpectationError, Scenario, ScenarioOutcome, ScenarioResult, run_chaos_scenario,
+};
+pub use scope::{
+    AsyncFailpointGuard, HitsGuard, enable_failpoint_scoped_async, expect_hits,
+    for_each_single_failpoint, scoped, with_action,
+};
+pub use session::{ChaosSession, FireRecord, replay, start_recording, stop_recording, stop_replay};
+pub use snapshot::{CheckpointGuard, ConfigDiff, FailpointSnapshot, TagState, diff_config};
+pub use timing::{ClockSkew, SkewClock, SleepExpectation, SleepStats, thread_cpu_time};
 
 #[cfg(test)]
 mod tests {
@@ -41,6 +115,114 @@ mod tests {
         with_failpoint!("fail_test", error, example());
     }
 
+    #[test]
+    fn test_maybe_io_fail() {
+        fn connect() -> std::io::Result<()> {
+            maybe_io_fail!("io_fail_test", std::io::ErrorKind::TimedOut);
+            Ok(())
+        }
+
+        assert!(connect().is_ok());
+
+        __failpoint_internal::enable_failpoint("io_fail_test");
+        let err = connect().unwrap_err();
+        __failpoint_internal::disable_failpoint("io_fail_test");
+
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_cooldown() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("cooldown_test");
+            Ok(())
+        }
+
+        configure_cooldown("cooldown_test", std::time::Duration::from_millis(100));
+        __failpoint_internal::enable_failpoint("cooldown_test");
+
+        assert!(call().is_err());
+        assert!(call().is_ok());
+        assert!(call().is_ok());
+
+        std::thread::sleep(std::time::Duration::from_millis(120));
+        assert!(call().is_err());
+        assert!(call().is_ok());
+
+        __failpoint_internal::disable_failpoint("cooldown_test");
+        clear_cooldown("cooldown_test");
+    }
+
+    #[test]
+    fn test_maybe_degrade() {
+        fn render(degraded: &mut bool) -> Result<&'static str, String> {
+            maybe_degrade!("degrade_test", || *degraded = true);
+            Ok("rendered")
+        }
+
+        let mut degraded = false;
+        assert_eq!(render(&mut degraded).unwrap(), "rendered");
+        assert!(!degraded);
+
+        __failpoint_internal::enable_failpoint("degrade_test");
+        assert_eq!(render(&mut degraded).unwrap(), "rendered");
+        __failpoint_internal::disable_failpoint("degrade_test");
+        assert!(degraded);
+    }
+
+    #[test]
+    fn test_maybe_short_write() {
+        let buf = [0u8; 100];
+        let mut written = 0;
+
+        maybe_short_write!("short_write_test", buf, &mut written);
+        assert_eq!(written, 100);
+
+        __failpoint_internal::enable_failpoint("short_write_test");
+        maybe_short_write!("short_write_test", buf, &mut written);
+        assert!(written < 100);
+
+        configure_write_fraction("short_write_test", 0.25);
+        maybe_short_write!("short_write_test", buf, &mut written);
+        assert_eq!(written, 25);
+        __failpoint_internal::disable_failpoint("short_write_test");
+    }
+
+    #[test]
+    fn test_probability() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("probability_test");
+            Ok(())
+        }
+
+        __failpoint_internal::enable_failpoint("probability_test");
+        configure_probability("probability_test", 0.0);
+        assert!((0..50).all(|_| call().is_ok()));
+
+        configure_probability("probability_test", 1.0);
+        assert!((0..50).all(|_| call().is_err()));
+
+        __failpoint_internal::disable_failpoint("probability_test");
+        clear_probability("probability_test");
+    }
+
+    #[test]
+    fn test_sustained_failures() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("sustained_test");
+            Ok(())
+        }
+
+        configure_cooldown("sustained_test", std::time::Duration::from_secs(60));
+        enable_failpoint_sustained("sustained_test");
+        for _ in 0..100 {
+            assert!(call().is_err());
+        }
+
+        __failpoint_internal::disable_failpoint("sustained_test");
+        assert!(call().is_ok());
+    }
+
     #[test]
     fn test_maybe_panic() {
         fn risky() {
@@ -52,6 +234,74 @@ mod tests {
         with_failpoint!("panic_test", panic, risky());
     }
 
+    #[test]
+    fn test_panic_payload_capture() {
+        #[derive(Debug, PartialEq)]
+        struct Custom(u32);
+
+        fn risky() {
+            if __failpoint_internal::is_failpoint_enabled("panic_payload_test") {
+                std::panic::panic_any(Custom(42));
+            }
+        }
+
+        with_failpoint!("panic_payload_test", panic, payload => risky());
+        assert_eq!(payload.downcast_ref::<Custom>(), Some(&Custom(42)));
+
+        with_failpoint!("panic_payload_str_test", panic, message => {
+            maybe_panic!("panic_payload_str_test");
+        });
+        assert_eq!(
+            message.downcast_ref::<&str>(),
+            Some(&"panic_payload_str_test")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_maybe_panic_async() {
+        async fn risky() {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            maybe_panic_async!("panic_async_test");
+        }
+
+        risky().await;
+
+        with_failpoint_async!("panic_async_test", panic, risky());
+
+        __failpoint_internal::enable_failpoint("panic_async_test");
+        let joined = tokio::spawn(risky()).await;
+        __failpoint_internal::disable_failpoint("panic_async_test");
+        assert!(joined.unwrap_err().is_panic());
+    }
+
+    #[tokio::test]
+    async fn test_with_failpoint_async_error() {
+        async fn fetch() -> Result<&'static str, String> {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            maybe_fail!("async_error_test");
+            Ok("fetched")
+        }
+
+        assert_eq!(fetch().await.unwrap(), "fetched");
+
+        with_failpoint_async!("async_error_test", error, fetch());
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "async_error_test"
+        ));
+
+        let cancelled = tokio::time::timeout(std::time::Duration::from_millis(10), async {
+            with_failpoint_async!("async_error_cancel_test", error, async {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                Err::<(), ()>(())
+            });
+        })
+        .await;
+        assert!(cancelled.is_err());
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "async_error_cancel_test"
+        ));
+    }
+
     #[test]
     fn test_maybe_sleep() {
         fn slow() {
@@ -64,4 +314,1738 @@ mod tests {
 
         with_failpoint!("sleep_test", 50, 10, slow());
     }
+
+    #[test]
+    fn test_maybe_sleep_range() {
+        fn jittery() {
+            maybe_sleep_range!("sleep_range_test", 20, 60);
+        }
+
+        with_failpoint_timed!(
+            "sleep_range_test",
+            SleepExpectation::between(20, 60).tolerance(20),
+            jittery()
+        );
+    }
+
+    #[test]
+    fn test_maybe_sleep_until() {
+        fn aligned(deadline: Instant) {
+            maybe_sleep_until!("sleep_until_test", deadline);
+        }
+
+        __failpoint_internal::enable_failpoint("sleep_until_test");
+        let deadline = Instant::now() + std::time::Duration::from_millis(50);
+        aligned(deadline);
+        let woke = Instant::now();
+
+        let start = Instant::now();
+        aligned(deadline);
+        let past = start.elapsed();
+        __failpoint_internal::disable_failpoint("sleep_until_test");
+
+        assert!(woke >= deadline);
+        assert!(woke - deadline < std::time::Duration::from_millis(30));
+        assert!(past.as_millis() < 10);
+    }
+
+    #[test]
+    fn test_maybe_slow() {
+        fn work() {
+            slow_begin("slow_test");
+            std::thread::sleep(std::time::Duration::from_millis(40));
+            maybe_slow!("slow_test", 2.5);
+        }
+
+        let start = Instant::now();
+        work();
+        assert!(start.elapsed() < std::time::Duration::from_millis(80));
+
+        __failpoint_internal::enable_failpoint("slow_test");
+        let start = Instant::now();
+        work();
+        let elapsed = start.elapsed();
+        __failpoint_internal::disable_failpoint("slow_test");
+
+        assert!(elapsed >= std::time::Duration::from_millis(100));
+        assert!(elapsed < std::time::Duration::from_millis(180));
+    }
+
+    #[test]
+    fn test_maybe_now() {
+        use std::time::{Duration, SystemTime};
+
+        fn now() -> SystemTime {
+            maybe_now!(
+                "clock_skew_test",
+                SystemTime::UNIX_EPOCH + Duration::from_secs(100)
+            )
+        }
+        fn instant(real: Instant) -> Instant {
+            maybe_now!("clock_skew_test", real)
+        }
+
+        let epoch = SystemTime::UNIX_EPOCH;
+        assert_eq!(now(), epoch + Duration::from_secs(100));
+
+        configure_clock_skew("clock_skew_test", ClockSkew::Ahead(Duration::from_secs(30)));
+        __failpoint_internal::enable_failpoint("clock_skew_test");
+        assert_eq!(now(), epoch + Duration::from_secs(130));
+
+        configure_clock_skew(
+            "clock_skew_test",
+            ClockSkew::Behind(Duration::from_secs(30)),
+        );
+        assert_eq!(now(), epoch + Duration::from_secs(70));
+        let real = Instant::now();
+        assert_eq!(real - instant(real), Duration::from_secs(30));
+
+        __failpoint_internal::disable_failpoint("clock_skew_test");
+        clear_clock_skew("clock_skew_test");
+    }
+
+    #[test]
+    fn test_with_failpoint_total_sleep() {
+        fn retries() {
+            for _ in 0..3 {
+                maybe_sleep!("total_sleep_unit_test", 20);
+            }
+            maybe_sleep_range!("total_sleep_unit_test", 10, 10);
+        }
+
+        with_failpoint!("total_sleep_unit_test", total_sleep(70, 1), retries());
+        let result = std::panic::catch_unwind(|| {
+            with_failpoint!("total_sleep_unit_test", total_sleep(20, 5), retries());
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_sleep_at_least() {
+        fn slow() {
+            maybe_sleep!("at_least_test", 20);
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+
+        with_failpoint!("at_least_test", at_least(20), slow());
+    }
+
+    #[test]
+    fn test_sleep_expectation_around() {
+        let expectation = SleepExpectation::around(200).tolerance(50);
+        assert_eq!(expectation.min(), std::time::Duration::from_millis(150));
+        assert_eq!(expectation.max(), std::time::Duration::from_millis(250));
+
+        fn slow() {
+            maybe_sleep!("timed_around_test", 50);
+        }
+
+        with_failpoint_timed!(
+            "timed_around_test",
+            SleepExpectation::around(50).tolerance(10),
+            slow()
+        );
+    }
+
+    #[test]
+    fn test_sleep_expectation_between() {
+        let expectation = SleepExpectation::between(150, 260);
+        assert!(expectation.contains(std::time::Duration::from_millis(150)));
+        assert!(expectation.contains(std::time::Duration::from_millis(260)));
+        assert!(!expectation.contains(std::time::Duration::from_millis(261)));
+
+        fn slow() {
+            maybe_sleep!("timed_between_test", 50);
+        }
+
+        with_failpoint_timed!(
+            "timed_between_test",
+            SleepExpectation::between(40, 70),
+            slow()
+        );
+    }
+
+    #[test]
+    fn test_init_from_args() {
+        let args = [
+            "app",
+            "--chaos-enable=args_enable_test",
+            "--chaos-sleep=args_sleep_test:20",
+            "--verbose",
+        ]
+        .map(String::from);
+
+        let rest = init_from_args(args.into_iter()).unwrap();
+        assert_eq!(rest, ["app", "--verbose"]);
+        assert!(__failpoint_internal::is_failpoint_enabled(
+            "args_enable_test"
+        ));
+        assert!(__failpoint_internal::is_failpoint_enabled(
+            "args_sleep_test"
+        ));
+
+        let start = Instant::now();
+        maybe_sleep!("args_sleep_test", 500);
+        assert!(start.elapsed().as_millis() < 400);
+
+        __failpoint_internal::disable_failpoint("args_enable_test");
+        __failpoint_internal::disable_failpoint("args_sleep_test");
+    }
+
+    #[test]
+    fn test_init_from_args_rejects_malformed_flags() {
+        let no_millis = ["--chaos-sleep=tag".to_string()];
+        assert!(init_from_args(no_millis.into_iter()).is_err());
+
+        let bad_millis = ["--chaos-sleep=tag:soon".to_string()];
+        assert!(init_from_args(bad_millis.into_iter()).is_err());
+
+        let empty_tag = ["--chaos-enable=".to_string()];
+        assert!(init_from_args(empty_tag.into_iter()).is_err());
+    }
+
+    #[test]
+    fn test_maybe_block() {
+        fn blocked() {
+            maybe_block!("block_test");
+        }
+
+        blocked();
+
+        __failpoint_internal::enable_failpoint("block_test");
+        let handle = std::thread::spawn(blocked);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!handle.is_finished());
+
+        release_failpoint("block_test");
+        handle.join().unwrap();
+        __failpoint_internal::disable_failpoint("block_test");
+    }
+
+    #[test]
+    fn test_disable_releases_block() {
+        fn blocked() {
+            maybe_block!("block_disable_test");
+        }
+
+        __failpoint_internal::enable_failpoint("block_disable_test");
+        let handle = std::thread::spawn(blocked);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!handle.is_finished());
+
+        __failpoint_internal::disable_failpoint("block_disable_test");
+        handle.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_with_failpoint_async_error_and_timing() {
+        async fn slow_fetch() -> Result<(), String> {
+            maybe_sleep_async!("async_error_timing_test", 50);
+            maybe_fail!("async_error_timing_test");
+            Ok(())
+        }
+
+        with_failpoint_async!(
+            "async_error_timing_test",
+            error_and_timing(40, 200),
+            slow_fetch()
+        );
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "async_error_timing_test"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_maybe_await_barrier() {
+        use std::sync::{Arc, Mutex};
+        use tokio::sync::Barrier;
+
+        async fn step(
+            name: &'static str,
+            delay_ms: u64,
+            barrier: Arc<Barrier>,
+            log: Arc<Mutex<Vec<String>>>,
+        ) {
+            tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+            log.lock().unwrap().push(format!("{name} arrived"));
+            maybe_await_barrier!("await_barrier_test", barrier);
+            log.lock().unwrap().push(format!("{name} passed"));
+        }
+
+        let barrier = Arc::new(Barrier::new(2));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        __failpoint_internal::enable_failpoint("await_barrier_test");
+        let fast = tokio::spawn(step("fast", 0, barrier.clone(), log.clone()));
+        let slow = tokio::spawn(step("slow", 50, barrier, log.clone()));
+        fast.await.unwrap();
+        slow.await.unwrap();
+        __failpoint_internal::disable_failpoint("await_barrier_test");
+
+        let log = log.lock().unwrap();
+        assert_eq!(log[..2], ["fast arrived", "slow arrived"]);
+    }
+
+    #[tokio::test]
+    async fn test_maybe_block_async_released_before_timeout() {
+        async fn blocked() {
+            maybe_block_async!(
+                "block_async_release_test",
+                std::time::Duration::from_secs(5)
+            );
+        }
+
+        __failpoint_internal::enable_failpoint("block_async_release_test");
+        let start = Instant::now();
+        let task = tokio::spawn(blocked());
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!task.is_finished());
+
+        release_failpoint("block_async_release_test");
+        task.await.unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        __failpoint_internal::disable_failpoint("block_async_release_test");
+    }
+
+    #[tokio::test]
+    async fn test_maybe_block_async_times_out() {
+        async fn blocked() {
+            maybe_block_async!(
+                "block_async_timeout_test",
+                std::time::Duration::from_millis(50)
+            );
+        }
+
+        __failpoint_internal::enable_failpoint("block_async_timeout_test");
+        let start = Instant::now();
+        blocked().await;
+        assert!(start.elapsed() >= std::time::Duration::from_millis(50));
+        assert!(!__failpoint_internal::has_block_state(
+            "block_async_timeout_test"
+        ));
+
+        let mut pending = Box::pin(blocked());
+        assert!(futures::poll!(pending.as_mut()).is_pending());
+        assert!(__failpoint_internal::has_block_state(
+            "block_async_timeout_test"
+        ));
+        drop(pending);
+        assert!(!__failpoint_internal::has_block_state(
+            "block_async_timeout_test"
+        ));
+
+        release_failpoint("block_async_timeout_test");
+        __failpoint_internal::disable_failpoint("block_async_timeout_test");
+    }
+
+    #[test]
+    fn test_failpoint_origin() {
+        assert!(failpoint_origin("origin_test").is_none());
+
+        __failpoint_internal::enable_failpoint("origin_test");
+        let line = line!() - 1;
+
+        let origin = failpoint_origin("origin_test").unwrap();
+        assert_eq!(origin.file(), file!());
+        assert_eq!(origin.line(), line);
+
+        __failpoint_internal::disable_failpoint("origin_test");
+        assert!(failpoint_origin("origin_test").is_none());
+
+        scoped("origin_scoped_test", || {
+            let origin = failpoint_origin("origin_scoped_test").unwrap();
+            assert_eq!(origin.line(), line!() - 2);
+        });
+    }
+
+    #[test]
+    fn test_chain_sleep_then_error() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("chain_error_test");
+            Ok(())
+        }
+
+        configure_chain(
+            "chain_error_test",
+            &[
+                Action::Sleep(std::time::Duration::from_millis(50)),
+                Action::Error,
+                Action::Sleep(std::time::Duration::from_secs(5)),
+            ],
+        );
+
+        let start = Instant::now();
+        with_failpoint!("chain_error_test", error, call());
+        let elapsed = start.elapsed();
+        assert!(elapsed >= std::time::Duration::from_millis(50));
+        assert!(elapsed < std::time::Duration::from_secs(1));
+
+        configure_chain(
+            "chain_error_test",
+            &[Action::Sleep(std::time::Duration::from_millis(1))],
+        );
+        __failpoint_internal::enable_failpoint("chain_error_test");
+        assert!(call().is_ok());
+        __failpoint_internal::disable_failpoint("chain_error_test");
+        clear_chain("chain_error_test");
+    }
+
+    #[test]
+    fn test_chain_sleep_then_panic() {
+        fn call() {
+            maybe_sleep!("chain_panic_test", 1);
+        }
+
+        configure_chain(
+            "chain_panic_test",
+            &[
+                Action::Sleep(std::time::Duration::from_millis(50)),
+                Action::Panic,
+            ],
+        );
+
+        let start = Instant::now();
+        with_failpoint!("chain_panic_test", panic, call());
+        assert!(start.elapsed() >= std::time::Duration::from_millis(50));
+        clear_chain("chain_panic_test");
+    }
+
+    #[test]
+    fn test_single_fire_per_group() {
+        fn a() -> Result<(), String> {
+            maybe_fail!("group_a_test");
+            Ok(())
+        }
+        fn b() -> Result<(), String> {
+            maybe_fail!("group_b_test");
+            Ok(())
+        }
+        fn c() -> Result<(), String> {
+            maybe_fail!("group_c_test");
+            Ok(())
+        }
+
+        let tags = ["group_a_test", "group_b_test", "group_c_test"];
+        for tag in tags {
+            set_failpoint_group(tag, "single_fire_group_test");
+            __failpoint_internal::enable_failpoint(tag);
+        }
+        set_single_fire_per_group("single_fire_group_test", true);
+
+        for _ in 0..20 {
+            begin_request();
+            let fired = [a(), b(), c()].iter().filter(|r| r.is_err()).count();
+            end_request();
+            assert_eq!(fired, 1);
+        }
+
+        let fired = [a(), b(), c()].iter().filter(|r| r.is_err()).count();
+        assert_eq!(fired, 3);
+
+        for tag in tags {
+            __failpoint_internal::disable_failpoint(tag);
+        }
+        set_single_fire_per_group("single_fire_group_test", false);
+    }
+
+    #[test]
+    fn test_run_chaos_scenario() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("scenario_test");
+            Ok(())
+        }
+
+        let result = run_chaos_scenario(
+            "retry exhausts",
+            || __failpoint_internal::enable_failpoint("scenario_test"),
+            || (0..3).map(|_| call()).collect::<Result<Vec<_>, _>>(),
+        );
+
+        assert_eq!(result.name, "retry exhausts");
+        assert!(result.failed);
+        assert!(!result.panicked);
+        assert_eq!(result.error.as_deref(), Some("\"scenario_test\""));
+        assert_eq!(result.hits.get("scenario_test"), Some(&1));
+        assert!(!__failpoint_internal::is_failpoint_enabled("scenario_test"));
+
+        let result = run_chaos_scenario(
+            "resets counters",
+            || {
+                __failpoint_internal::enable_failpoint("scenario_test");
+                let _ = call();
+            },
+            || -> Result<(), ()> {
+                reset_failpoint_count("scenario_test");
+                Ok(())
+            },
+        );
+        assert_eq!(result.hits.get("scenario_test"), Some(&0));
+
+        let result = run_chaos_scenario(
+            "panics",
+            || __failpoint_internal::enable_failpoint("scenario_panic_test"),
+            || -> Result<(), ()> {
+                maybe_panic!("scenario_panic_test");
+                Ok(())
+            },
+        );
+        assert!(result.failed && result.panicked);
+        assert_eq!(result.error.as_deref(), Some("scenario_panic_test"));
+    }
+
+    #[test]
+    fn test_assert_failpoint_hits() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("hits_test");
+            Ok(())
+        }
+
+        scoped("hits_test", || {
+            for _ in 0..3 {
+                let _ = call();
+            }
+        });
+
+        assert_failpoint_hits!("hits_test", 3);
+        assert_failpoint_hits_at_least!("hits_test", 2);
+        assert_failpoint_hits_at_least!("hits_test", 3);
+
+        let fewer = std::panic::catch_unwind(|| assert_failpoint_hits!("hits_test", 2));
+        assert!(fewer.is_err());
+        let more = std::panic::catch_unwind(|| assert_failpoint_hits!("hits_test", 4));
+        assert!(more.is_err());
+        let more_at_least =
+            std::panic::catch_unwind(|| assert_failpoint_hits_at_least!("hits_test", 4));
+        assert!(more_at_least.is_err());
+
+        reset_failpoint_count("hits_test");
+        assert_failpoint_hits!("hits_test", 0);
+    }
+
+    #[test]
+    fn test_with_action_nested() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("with_action_test");
+            Ok(())
+        }
+
+        let sleep = Action::Sleep(std::time::Duration::from_millis(30));
+        __failpoint_internal::enable_failpoint("with_action_test");
+
+        with_action("with_action_test", sleep, || {
+            let start = Instant::now();
+            assert!(call().is_ok());
+            assert!(start.elapsed() >= std::time::Duration::from_millis(30));
+
+            with_action("with_action_test", Action::Error, || {
+                assert!(call().is_err());
+            });
+
+            assert_eq!(
+                __failpoint_internal::chain("with_action_test"),
+                Some(vec![sleep])
+            );
+            assert!(call().is_ok());
+        });
+
+        assert_eq!(__failpoint_internal::chain("with_action_test"), None);
+        assert!(call().is_err());
+        __failpoint_internal::disable_failpoint("with_action_test");
+    }
+
+    #[test]
+    fn test_reset_failpoint_count_prefix() {
+        fn read() -> Result<(), String> {
+            maybe_fail!("prefix_storage::read");
+            Ok(())
+        }
+        fn write() -> Result<(), String> {
+            maybe_fail!("prefix_storage::write");
+            Ok(())
+        }
+        fn net() -> Result<(), String> {
+            maybe_fail!("prefix_net::send");
+            Ok(())
+        }
+
+        for tag in [
+            "prefix_storage::read",
+            "prefix_storage::write",
+            "prefix_net::send",
+        ] {
+            __failpoint_internal::enable_failpoint(tag);
+        }
+        for _ in 0..2 {
+            let _ = read();
+            let _ = write();
+            let _ = net();
+        }
+
+        reset_failpoint_count_prefix("prefix_storage::");
+        assert_failpoint_hits!("prefix_storage::read", 0);
+        assert_failpoint_hits!("prefix_storage::write", 0);
+        assert_failpoint_hits!("prefix_net::send", 2);
+
+        for tag in [
+            "prefix_storage::read",
+            "prefix_storage::write",
+            "prefix_net::send",
+        ] {
+            __failpoint_internal::disable_failpoint(tag);
+        }
+    }
+
+    #[test]
+    fn test_fail_iter() {
+        let source = || (1..=3).map(Ok::<u32, String>);
+
+        let passthrough: Vec<_> = fail_iter(source(), "iter_test", || "boom".into()).collect();
+        assert_eq!(passthrough, [Ok(1), Ok(2), Ok(3)]);
+
+        let mut iter = fail_iter(source(), "iter_test", || "boom".to_string());
+        assert_eq!(iter.next(), Some(Ok(1)));
+        __failpoint_internal::enable_failpoint("iter_test");
+        assert_eq!(iter.next(), Some(Err("boom".to_string())));
+        __failpoint_internal::disable_failpoint("iter_test");
+        assert_eq!(iter.next(), Some(Ok(3)));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_maybe_fail_any() {
+        fn call() -> Result<(), &'static str> {
+            maybe_fail_any!(["any_a_test", "any_b_test"], "unreachable");
+            Ok(())
+        }
+
+        assert_eq!(call(), Ok(()));
+        __failpoint_internal::enable_failpoint("any_b_test");
+        let result = call();
+        __failpoint_internal::disable_failpoint("any_b_test");
+
+        assert_eq!(result, Err("unreachable"));
+        assert_eq!(failpoint_hits("any_a_test"), 0);
+    }
+
+    #[test]
+    fn test_maybe_fail_after_unlock() {
+        use std::sync::Mutex;
+
+        fn update(balance: &Mutex<u64>) -> Result<(), &'static str> {
+            let mut guard = balance.lock().unwrap();
+            *guard += 10;
+            maybe_fail_after_unlock!("after_unlock_test", guard, "crashed");
+            *guard -= 1;
+            Ok(())
+        }
+
+        let balance = Mutex::new(0);
+        assert_eq!(update(&balance), Ok(()));
+
+        __failpoint_internal::enable_failpoint("after_unlock_test");
+        let result = update(&balance);
+        __failpoint_internal::disable_failpoint("after_unlock_test");
+
+        assert_eq!(result, Err("crashed"));
+        assert!(!balance.is_poisoned());
+        assert_eq!(*balance.try_lock().unwrap(), 19);
+    }
+
+    #[test]
+    fn test_maybe_fail_cycle() {
+        fn call() -> Result<(), &'static str> {
+            maybe_fail_cycle!("cycle_test", ["a", "b", "c"]);
+            Ok(())
+        }
+
+        assert_eq!(call(), Ok(()));
+        __failpoint_internal::enable_failpoint("cycle_test");
+        let seen: Vec<_> = (0..4).map(|_| call()).collect();
+        __failpoint_internal::disable_failpoint("cycle_test");
+
+        assert_eq!(seen, [Err("a"), Err("b"), Err("c"), Err("a")]);
+    }
+
+    #[test]
+    fn test_maybe_box_fail() {
+        fn load() -> Result<u32, Box<dyn std::error::Error>> {
+            maybe_box_fail!("box_fail_test");
+            Ok(1)
+        }
+
+        assert_eq!(load().unwrap(), 1);
+        __failpoint_internal::enable_failpoint("box_fail_test");
+        let err = load().unwrap_err();
+        __failpoint_internal::disable_failpoint("box_fail_test");
+
+        assert_eq!(err.to_string(), "box_fail_test");
+    }
+
+    #[test]
+    fn test_maybe_alloc_fail() {
+        fn reserve(buf: &mut Vec<u8>) -> Result<(), AllocError> {
+            maybe_alloc_fail!("alloc_test");
+            buf.reserve(64);
+            Ok(())
+        }
+
+        let mut buf = Vec::new();
+        assert_eq!(reserve(&mut buf), Ok(()));
+
+        __failpoint_internal::enable_failpoint("alloc_test");
+        let err = reserve(&mut Vec::new()).unwrap_err();
+        __failpoint_internal::disable_failpoint("alloc_test");
+
+        assert_eq!(err.tag(), "alloc_test");
+        assert_eq!(
+            err.to_string(),
+            "injected allocation failure at 'alloc_test'"
+        );
+    }
+
+    #[test]
+    fn test_scenario_expectations() {
+        fn fetch() -> Result<(), String> {
+            maybe_fail!("fluent_error_test");
+            Ok(())
+        }
+
+        Scenario::new("fluent_error_test")
+            .expect_error()
+            .run(fetch)
+            .unwrap()
+            .run(fetch)
+            .unwrap();
+        let err = Scenario::new("fluent_unused_test")
+            .expect_error()
+            .run(fetch)
+            .unwrap_err();
+        assert!(err.to_string().contains("fluent_unused_test"));
+
+        Scenario::new("fluent_panic_test")
+            .expect_panic()
+            .run(|| {
+                maybe_panic!("fluent_panic_test");
+            })
+            .unwrap();
+        assert!(
+            Scenario::new("fluent_panic_test")
+                .expect_panic()
+                .run(|| ())
+                .is_err()
+        );
+
+        Scenario::new("fluent_sleep_test")
+            .expect_sleep(40, 200)
+            .run(|| {
+                maybe_sleep!("fluent_sleep_test", 50);
+            })
+            .unwrap();
+        assert!(
+            Scenario::new("fluent_sleep_test")
+                .expect_sleep(40, 200)
+                .run(|| ())
+                .is_err()
+        );
+
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "fluent_error_test"
+        ));
+    }
+
+    #[test]
+    fn test_enable_failpoint_if_debug() {
+        let enabled = enable_failpoint_if_debug("debug_only_test");
+
+        assert_eq!(enabled, cfg!(debug_assertions));
+        assert_eq!(
+            __failpoint_internal::is_failpoint_enabled("debug_only_test"),
+            cfg!(debug_assertions)
+        );
+        __failpoint_internal::disable_failpoint("debug_only_test");
+    }
+
+    #[test]
+    fn test_on_state_change() {
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        on_state_change(Box::new(move |tag, enabled| {
+            if tag == "state_change_test" {
+                sink.lock().unwrap().push(enabled);
+                if enabled {
+                    // Reentrant: toggling another tag from inside the callback is allowed.
+                    __failpoint_internal::enable_failpoint("state_change_nested_test");
+                }
+            }
+        }));
+
+        __failpoint_internal::enable_failpoint("state_change_test");
+        __failpoint_internal::enable_failpoint("state_change_test");
+        __failpoint_internal::disable_failpoint("state_change_test");
+        __failpoint_internal::disable_failpoint("state_change_test");
+        enable_failpoint_limited("state_change_test", 0, std::time::Duration::from_secs(60));
+
+        assert_eq!(*seen.lock().unwrap(), [true, false]);
+        assert!(__failpoint_internal::is_failpoint_enabled(
+            "state_change_nested_test"
+        ));
+        __failpoint_internal::disable_failpoint("state_change_nested_test");
+    }
+
+    #[test]
+    fn test_configure_per_thread() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("per_thread_test");
+            Ok(())
+        }
+
+        let erroring = std::thread::current().id();
+        configure_per_thread(
+            "per_thread_test",
+            Box::new(move |id| {
+                if id == erroring {
+                    Action::Error
+                } else {
+                    Action::Sleep(std::time::Duration::from_millis(1))
+                }
+            }),
+        );
+        __failpoint_internal::enable_failpoint("per_thread_test");
+
+        let other = std::thread::spawn(call).join().unwrap();
+        let own = call();
+        __failpoint_internal::disable_failpoint("per_thread_test");
+        clear_per_thread("per_thread_test");
+
+        assert_eq!(other, Ok(()));
+        assert!(own.is_err());
+    }
+
+    #[test]
+    fn test_maybe_fail_on_poll() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+
+        struct ThreePolls(u32);
+
+        impl Future for ThreePolls {
+            type Output = Result<u32, &'static str>;
+
+            fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+                maybe_fail_on_poll!("fail_on_poll_test", 2, "second poll");
+                self.0 += 1;
+                if self.0 < 3 {
+                    Poll::Pending
+                } else {
+                    Poll::Ready(Ok(self.0))
+                }
+            }
+        }
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut poll_all = || {
+            let mut future = ThreePolls(0);
+            std::iter::from_fn(|| Some(Pin::new(&mut future).poll(&mut cx)))
+                .take(3)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(poll_all()[2], Poll::Ready(Ok(3)));
+        assert!(known_failpoints().contains(&"fail_on_poll_test".to_string()));
+
+        __failpoint_internal::enable_failpoint("fail_on_poll_test");
+        let polls = poll_all();
+        __failpoint_internal::disable_failpoint("fail_on_poll_test");
+
+        assert_eq!(polls[..2], [Poll::Pending, Poll::Ready(Err("second poll"))]);
+    }
+
+    #[test]
+    fn test_enable_failpoint_limited() {
+        fn by_count() -> Result<(), String> {
+            maybe_fail!("limited_count_test");
+            Ok(())
+        }
+        fn by_time() -> Result<(), String> {
+            maybe_fail!("limited_time_test");
+            Ok(())
+        }
+
+        enable_failpoint_limited("limited_count_test", 2, std::time::Duration::from_secs(60));
+        assert!(by_count().is_err());
+        assert!(by_count().is_err());
+        assert!(by_count().is_ok());
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "limited_count_test"
+        ));
+
+        enable_failpoint_limited(
+            "limited_time_test",
+            1_000,
+            std::time::Duration::from_millis(30),
+        );
+        assert!(by_time().is_err());
+        std::thread::sleep(std::time::Duration::from_millis(40));
+        assert!(by_time().is_ok());
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "limited_time_test"
+        ));
+    }
+
+    #[test]
+    fn test_enable_failpoint_limited_without_time_limit() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("limited_forever_test");
+            Ok(())
+        }
+
+        enable_failpoint_limited("limited_forever_test", 2, std::time::Duration::MAX);
+        assert!(call().is_err());
+        assert!(call().is_err());
+        assert!(call().is_ok());
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "limited_forever_test"
+        ));
+    }
+
+    #[test]
+    fn test_failpoint_barrier() {
+        use std::sync::{Arc, Mutex};
+
+        failpoint_barrier(
+            "barrier_a_test",
+            "barrier_b_test",
+            std::time::Duration::from_secs(5),
+        );
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let log = Arc::clone(&order);
+        let a = std::thread::spawn(move || {
+            maybe_degrade!("barrier_a_test", || ());
+            log.lock().unwrap().push("a");
+        });
+        let log = Arc::clone(&order);
+        let b = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            log.lock().unwrap().push("b");
+            maybe_degrade!("barrier_b_test", || ());
+        });
+
+        a.join().unwrap();
+        b.join().unwrap();
+        assert_eq!(*order.lock().unwrap(), ["b", "a"]);
+    }
+
+    #[test]
+    fn test_maybe_partial_fail() {
+        fn commit(batch: &[u32]) -> (Vec<u32>, Option<(usize, &'static str)>) {
+            let mut committed = batch.to_vec();
+            let failure = maybe_partial_fail!("partial_fail_test", &mut committed, "disk full");
+            (committed, failure)
+        }
+
+        let batch = [1, 2, 3, 4, 5];
+        assert_eq!(commit(&batch), (batch.to_vec(), None));
+
+        __failpoint_internal::enable_failpoint("partial_fail_test");
+        let (committed, failure) = commit(&batch);
+        __failpoint_internal::disable_failpoint("partial_fail_test");
+
+        assert!(committed.len() < batch.len());
+        assert_eq!(committed, batch[..committed.len()]);
+        assert_eq!(failure, Some((batch.len() - committed.len(), "disk full")));
+    }
+
+    #[test]
+    fn test_known_failpoints() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("known_disabled_test");
+            Ok(())
+        }
+
+        assert!(!known_failpoints().contains(&"known_disabled_test".to_string()));
+        call().unwrap();
+        assert!(known_failpoints().contains(&"known_disabled_test".to_string()));
+    }
+
+    #[test]
+    fn test_enable_failpoint_direction() {
+        fn read() -> Result<(), &'static str> {
+            maybe_fail_read!("direction_test", "read");
+            Ok(())
+        }
+        fn write() -> Result<(), &'static str> {
+            maybe_fail_write!("direction_test", "write");
+            Ok(())
+        }
+
+        enable_failpoint_direction("direction_test", Direction::Read);
+        assert_eq!(read(), Err("read"));
+        assert_eq!(write(), Ok(()));
+
+        __failpoint_internal::enable_failpoint("direction_test");
+        assert_eq!(write(), Err("write"));
+        __failpoint_internal::disable_failpoint("direction_test");
+
+        fn write_only() -> Result<(), &'static str> {
+            maybe_fail_write!("direction_write_only_test", "write");
+            Ok(())
+        }
+        enable_failpoint_direction("direction_write_only_test", Direction::Read);
+        assert_eq!(write_only(), Ok(()));
+        __failpoint_internal::disable_failpoint("direction_write_only_test");
+        assert!(known_failpoints().contains(&"direction_write_only_test".to_string()));
+    }
+
+    #[test]
+    fn test_configure_escalation() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("escalation_test");
+            Ok(())
+        }
+
+        configure_escalation(
+            "escalation_test",
+            &[
+                (
+                    std::time::Duration::ZERO,
+                    Action::Sleep(std::time::Duration::from_millis(1)),
+                ),
+                (std::time::Duration::from_millis(50), Action::Error),
+                (std::time::Duration::from_millis(100), Action::Panic),
+            ],
+        );
+        __failpoint_internal::enable_failpoint("escalation_test");
+
+        assert!(call().is_ok());
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        assert!(call().is_err());
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(std::panic::catch_unwind(call).is_err());
+
+        __failpoint_internal::disable_failpoint("escalation_test");
+        clear_escalation("escalation_test");
+    }
+
+    #[test]
+    fn test_expect_hits() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("expect_hits_test");
+            Ok(())
+        }
+
+        __failpoint_internal::enable_failpoint("expect_hits_test");
+        {
+            let _hits = expect_hits("expect_hits_test", 2);
+            assert!(call().is_err());
+            assert!(call().is_err());
+        }
+
+        let mismatch = std::panic::catch_unwind(|| {
+            let _hits = expect_hits("expect_hits_test", 2);
+            let _ = call();
+        });
+        __failpoint_internal::disable_failpoint("expect_hits_test");
+
+        assert!(mismatch.is_err());
+    }
+
+    #[test]
+    fn test_maybe_reorder() {
+        fn run() -> (Vec<&'static str>, (u32, u32)) {
+            let log = std::cell::RefCell::new(Vec::new());
+            let results = maybe_reorder!(
+                "reorder_test",
+                {
+                    log.borrow_mut().push("a");
+                    1
+                },
+                {
+                    log.borrow_mut().push("b");
+                    2
+                }
+            );
+            (log.into_inner(), results)
+        }
+
+        assert_eq!(run(), (vec!["a", "b"], (1, 2)));
+        __failpoint_internal::enable_failpoint("reorder_test");
+        let swapped = run();
+        __failpoint_internal::disable_failpoint("reorder_test");
+
+        assert_eq!(swapped, (vec!["b", "a"], (1, 2)));
+    }
+
+    #[test]
+    fn test_register_child() {
+        fn read() -> Result<(), String> {
+            maybe_fail!("storage_test::read");
+            Ok(())
+        }
+        fn write() -> Result<(), String> {
+            maybe_fail!("storage_test::write");
+            Ok(())
+        }
+
+        register_child("storage_test", "storage_test::read");
+        register_child("storage_test", "storage_test::write");
+
+        __failpoint_internal::enable_failpoint("storage_test");
+        assert!(read().is_err());
+        assert!(write().is_err());
+
+        __failpoint_internal::disable_failpoint("storage_test");
+        assert!(read().is_ok());
+        assert!(write().is_ok());
+    }
+
+    #[test]
+    fn test_push_pop_suppress() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("push_suppress_test");
+            Ok(())
+        }
+
+        __failpoint_internal::enable_failpoint("push_suppress_test");
+        let results = std::thread::spawn(|| {
+            let mut results = vec![call().is_ok()];
+            push_suppress();
+            push_suppress();
+            results.push(call().is_ok());
+            pop_suppress();
+            results.push(call().is_ok());
+            pop_suppress();
+            results.push(call().is_ok());
+            pop_suppress();
+            results.push(call().is_ok());
+            results
+        })
+        .join()
+        .unwrap();
+        __failpoint_internal::disable_failpoint("push_suppress_test");
+
+        assert_eq!(results, [false, true, true, false, false]);
+    }
+
+    #[test]
+    fn test_suppress_chaos_on_current_thread() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("suppress_test");
+            Ok(())
+        }
+
+        __failpoint_internal::enable_failpoint("suppress_test");
+        let suppressed = std::thread::spawn(|| {
+            suppress_chaos_on_current_thread();
+            let suppressed = call();
+            resume_chaos_on_current_thread();
+            (suppressed, call())
+        })
+        .join()
+        .unwrap();
+        let normal = std::thread::spawn(call).join().unwrap();
+        __failpoint_internal::disable_failpoint("suppress_test");
+
+        assert!(suppressed.0.is_ok());
+        assert!(suppressed.1.is_err());
+        assert!(normal.is_err());
+    }
+
+    #[test]
+    fn test_with_failpoint_error_matching() {
+        #[derive(Debug, PartialEq)]
+        enum FetchError {
+            Timeout,
+            Refused,
+        }
+
+        fn fetch() -> Result<(), FetchError> {
+            maybe_fail!("error_matching_test", FetchError::Timeout);
+            Ok(())
+        }
+
+        with_failpoint!(
+            "error_matching_test",
+            error_matching(|e| *e == FetchError::Timeout),
+            fetch()
+        );
+
+        let mismatch = std::panic::catch_unwind(|| {
+            with_failpoint!(
+                "error_matching_test",
+                error_matching(|e| *e == FetchError::Refused),
+                fetch()
+            );
+        });
+        assert!(mismatch.is_err());
+    }
+
+    #[test]
+    fn test_maybe_fail_in_drop() {
+        struct Flusher<'a> {
+            flushed: &'a std::cell::Cell<bool>,
+        }
+
+        impl Drop for Flusher<'_> {
+            fn drop(&mut self) {
+                if maybe_fail_in_drop!("fail_in_drop_test") {
+                    return;
+                }
+                self.flushed.set(true);
+            }
+        }
+
+        let flushed = std::cell::Cell::new(false);
+        drop(Flusher { flushed: &flushed });
+        assert!(flushed.get());
+        assert!(take_drop_failures().is_empty());
+
+        flushed.set(false);
+        __failpoint_internal::enable_failpoint("fail_in_drop_test");
+        drop(Flusher { flushed: &flushed });
+        __failpoint_internal::disable_failpoint("fail_in_drop_test");
+        assert!(!flushed.get());
+        assert_eq!(take_drop_failures(), vec!["fail_in_drop_test"]);
+        assert!(take_drop_failures().is_empty());
+    }
+
+    #[test]
+    fn test_maybe_flap() {
+        fn ping() -> Result<(), String> {
+            maybe_flap!("flap_test", "down".to_string());
+            Ok(())
+        }
+
+        assert!(ping().is_ok());
+        __failpoint_internal::enable_failpoint("flap_test");
+        let results: Vec<bool> = (0..5).map(|_| ping().is_ok()).collect();
+        __failpoint_internal::disable_failpoint("flap_test");
+        assert_eq!(results, vec![false, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_assert_eventually_succeeds() {
+        fn connect(attempts: &mut u32) -> Result<(), String> {
+            *attempts += 1;
+            maybe_fail_times!("eventually_test", 2, "not ready".to_string());
+            Ok(())
+        }
+
+        let mut attempts = 0;
+        assert_eventually_succeeds!("eventually_test", 5, || connect(&mut attempts));
+        assert_eq!(attempts, 3);
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "eventually_test"
+        ));
+
+        let exhausted = std::panic::catch_unwind(|| {
+            let mut attempts = 0;
+            assert_eventually_succeeds!("eventually_test", 2, || connect(&mut attempts));
+        });
+        assert!(exhausted.is_err());
+
+        fn warm_up() -> Result<(), String> {
+            maybe_fail_times!("fail_times_zero_test", 0, "not ready".to_string());
+            Ok(())
+        }
+        __failpoint_internal::enable_failpoint("fail_times_zero_test");
+        assert!(warm_up().is_ok());
+        __failpoint_internal::disable_failpoint("fail_times_zero_test");
+        assert_eq!(failpoint_hits("fail_times_zero_test"), 0);
+    }
+
+    #[test]
+    fn test_maybe_fail_code() {
+        fn call() -> Result<(), CodedError> {
+            maybe_fail_code!("fail_code_test", 503);
+            Ok(())
+        }
+
+        assert!(call().is_ok());
+        __failpoint_internal::enable_failpoint("fail_code_test");
+        let err = call().unwrap_err();
+        __failpoint_internal::disable_failpoint("fail_code_test");
+        assert_eq!(err.code(), 503);
+        assert_eq!(err.tag(), "fail_code_test");
+        assert_eq!(err.to_string(), "injected error 503 at 'fail_code_test'");
+    }
+
+    #[test]
+    fn test_maybe_fail_hashed() {
+        fn load(id: u64) -> Result<u64, String> {
+            maybe_fail_hashed!("hashed_test", id, 0.3, "shard offline".to_string());
+            Ok(id)
+        }
+
+        assert!((0..100).all(|id| load(id).is_ok()));
+        assert!(known_failpoints().contains(&"hashed_test".to_string()));
+
+        __failpoint_internal::enable_failpoint("hashed_test");
+        let first: Vec<bool> = (0..1000).map(|id| load(id).is_err()).collect();
+        let second: Vec<bool> = (0..1000).map(|id| load(id).is_err()).collect();
+        __failpoint_internal::disable_failpoint("hashed_test");
+
+        assert_eq!(first, second);
+        let failed = first.iter().filter(|failed| **failed).count();
+        assert!((200..400).contains(&failed), "{failed} of 1000 keys failed");
+
+        // Pinned so that a change to the hash, which would reshuffle every user's subset,
+        // fails here.
+        let selected: Vec<u64> = (0..20u64)
+            .filter(|id| __failpoint_internal::hashed_allows("hashed_test", id, 0.3))
+            .collect();
+        assert_eq!(selected, [0, 4, 5, 6, 9, 10, 13, 15, 18]);
+    }
+
+    #[test]
+    fn test_maybe_transform() {
+        fn load() -> String {
+            maybe_transform!("transform_test", "payload".to_string(), |mut v: String| {
+                v.truncate(3);
+                v
+            })
+        }
+
+        assert_eq!(load(), "payload");
+        __failpoint_internal::enable_failpoint("transform_test");
+        assert_eq!(load(), "pay");
+        __failpoint_internal::disable_failpoint("transform_test");
+        assert_eq!(load(), "payload");
+    }
+
+    #[test]
+    fn test_chaos_closure() {
+        let offset = 10;
+        let run = || -> Result<Vec<u32>, String> {
+            [1, 2, 3]
+                .into_iter()
+                .map(chaos_closure!("closure_test", move |x: u32| Ok(x + offset)))
+                .collect()
+        };
+
+        assert_eq!(run().unwrap(), [11, 12, 13]);
+        __failpoint_internal::enable_failpoint("closure_test");
+        assert_eq!(run().unwrap_err(), "closure_test");
+        __failpoint_internal::disable_failpoint("closure_test");
+    }
+
+    #[test]
+    fn test_maybe_truncate_read() {
+        use std::io::Read;
+
+        struct Choppy<R>(R);
+
+        impl<R: Read> Read for Choppy<R> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let buf = maybe_truncate_read!("truncate_read_test", buf);
+                self.0.read(buf)
+            }
+        }
+
+        struct Fixed<R>(R);
+
+        impl<R: Read> Read for Fixed<R> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let buf = maybe_truncate_read!("truncate_read_fixed_test", buf, 3);
+                self.0.read(buf)
+            }
+        }
+
+        let data = [7u8; 64];
+        let mut buf = [0u8; 16];
+        assert_eq!(Choppy(&data[..]).read(&mut buf).unwrap(), 16);
+
+        __failpoint_internal::enable_failpoint("truncate_read_test");
+        for _ in 0..20 {
+            let read = Choppy(&data[..]).read(&mut buf).unwrap();
+            assert!((1..16).contains(&read), "read {read} bytes");
+        }
+        __failpoint_internal::disable_failpoint("truncate_read_test");
+
+        __failpoint_internal::enable_failpoint("truncate_read_fixed_test");
+        let mut buf = [0u8; 16];
+        assert_eq!(Fixed(&data[..]).read(&mut buf).unwrap(), 3);
+        __failpoint_internal::disable_failpoint("truncate_read_fixed_test");
+        assert_eq!(&buf[..4], &[7, 7, 7, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_enable_failpoint_scoped_async() {
+        let guard = enable_failpoint_scoped_async("scoped_async_test");
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        assert!(__failpoint_internal::is_failpoint_enabled(
+            "scoped_async_test"
+        ));
+        guard.await;
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "scoped_async_test"
+        ));
+
+        let task = tokio::spawn(async {
+            let _guard = enable_failpoint_scoped_async("scoped_async_cancel_test");
+            std::future::pending::<()>().await;
+        });
+        while !__failpoint_internal::is_failpoint_enabled("scoped_async_cancel_test") {
+            tokio::task::yield_now().await;
+        }
+        task.abort();
+        assert!(task.await.unwrap_err().is_cancelled());
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "scoped_async_cancel_test"
+        ));
+    }
+
+    #[test]
+    fn test_maybe_duplicate() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let publish = |id: u32| maybe_duplicate!("duplicate_test", id, |m| tx.send(m));
+
+        publish(1).unwrap();
+        __failpoint_internal::enable_failpoint("duplicate_test");
+        publish(2).unwrap();
+        __failpoint_internal::disable_failpoint("duplicate_test");
+        publish(3).unwrap();
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [1, 2, 2, 3]);
+    }
+
+    #[test]
+    fn test_diff_config() {
+        __failpoint_internal::configure_sleep(
+            "diff_changed_test",
+            std::time::Duration::from_millis(5),
+        );
+        let before = FailpointSnapshot::capture();
+
+        __failpoint_internal::enable_failpoint("diff_added_test");
+        __failpoint_internal::configure_sleep(
+            "diff_changed_test",
+            std::time::Duration::from_millis(9),
+        );
+        let diff = diff_config(&before);
+        __failpoint_internal::disable_failpoint("diff_added_test");
+
+        assert!(diff.added.contains(&"diff_added_test"));
+        assert!(diff.changed.contains(&"diff_changed_test"));
+        assert!(!diff.removed.contains(&"diff_added_test"));
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn test_configure_decay() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("decay_test");
+            Ok(())
+        }
+        fn fire_rate() -> f64 {
+            (0..400).filter(|_| call().is_err()).count() as f64 / 400.0
+        }
+
+        __failpoint_internal::configure_decay(
+            "decay_test",
+            1.0,
+            std::time::Duration::from_millis(100),
+        );
+        __failpoint_internal::enable_failpoint("decay_test");
+        let early = fire_rate();
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        let late = fire_rate();
+        __failpoint_internal::disable_failpoint("decay_test");
+        __failpoint_internal::clear_decay("decay_test");
+
+        assert!(early > 0.8, "early fire rate {early}");
+        assert!(late < 0.3, "late fire rate {late}");
+    }
+
+    #[test]
+    fn test_for_each_single_failpoint() {
+        let tags = ["single_a_test", "single_b_test", "single_c_test"];
+        let mut runs = Vec::new();
+        for_each_single_failpoint(&tags, |tag| {
+            let enabled: Vec<&str> = tags
+                .iter()
+                .copied()
+                .filter(|tag| __failpoint_internal::is_failpoint_enabled(tag))
+                .collect();
+            runs.push((tag, enabled));
+        });
+
+        assert_eq!(
+            runs,
+            [
+                ("single_a_test", vec!["single_a_test"]),
+                ("single_b_test", vec!["single_b_test"]),
+                ("single_c_test", vec!["single_c_test"]),
+            ]
+        );
+        assert!(
+            tags.iter()
+                .all(|tag| !__failpoint_internal::is_failpoint_enabled(tag))
+        );
+    }
+
+    #[test]
+    fn test_with_failpoint_no_panic() {
+        fn lookup() -> Option<u32> {
+            maybe_degrade!("no_panic_test", || {});
+            Some(1)
+        }
+
+        with_failpoint!("no_panic_test", no_panic, lookup());
+
+        let crashed = std::panic::catch_unwind(|| {
+            with_failpoint!("no_panic_crash_test", no_panic, {
+                maybe_panic!("no_panic_crash_test");
+            });
+        });
+        let message = __failpoint_internal::panic_message(&*crashed.unwrap_err());
+        assert!(message.contains("no_panic_crash_test"), "{message}");
+    }
+
+    #[test]
+    fn test_configure_latency_histogram() {
+        let fast = std::time::Duration::from_millis(1);
+        let slow = std::time::Duration::from_millis(4);
+        __failpoint_internal::configure_latency_histogram(
+            "histogram_test",
+            &[
+                (fast, 3),
+                (std::time::Duration::from_millis(2), 0),
+                (slow, 1),
+            ],
+        );
+
+        let samples: Vec<_> = (0..4000)
+            .map(|_| {
+                __failpoint_internal::sleep_duration(
+                    "histogram_test",
+                    std::time::Duration::from_millis(100),
+                )
+            })
+            .collect();
+        assert!(samples.iter().all(|d| *d == fast || *d == slow));
+        let fast_share = samples.iter().filter(|d| **d == fast).count() as f64 / 4000.0;
+        assert!((0.7..0.8).contains(&fast_share), "fast share {fast_share}");
+
+        with_failpoint!("histogram_test", at_least(1), {
+            maybe_sleep!("histogram_test", 100);
+        });
+        let stats = sleep_stats("histogram_test");
+        assert!(stats.max == fast || stats.max == slow);
+        __failpoint_internal::clear_latency_histogram("histogram_test");
+    }
+
+    #[test]
+    fn test_configure_recovery() {
+        let delay = || {
+            __failpoint_internal::sleep_duration(
+                "recovery_test",
+                std::time::Duration::from_millis(500),
+            )
+        };
+        __failpoint_internal::configure_recovery(
+            "recovery_test",
+            100,
+            std::time::Duration::from_millis(200),
+        );
+
+        __failpoint_internal::enable_failpoint("recovery_test");
+        let start = delay();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let middle = delay();
+        std::thread::sleep(std::time::Duration::from_millis(150));
+        let end = delay();
+        __failpoint_internal::disable_failpoint("recovery_test");
+        __failpoint_internal::clear_recovery("recovery_test");
+
+        assert!(start > std::time::Duration::from_millis(80), "{start:?}");
+        assert!(middle < start && middle <= std::time::Duration::from_millis(50));
+        assert_eq!(end, std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn test_with_failpoint_failure_context() {
+        fn call() -> Result<(), String> {
+            maybe_fail!("context_test");
+            Ok(())
+        }
+
+        let mismatch = std::panic::catch_unwind(|| {
+            with_failpoint!("context_test", no_panic, {
+                if call().is_err() {
+                    panic!("boom");
+                }
+            });
+        });
+        let message = __failpoint_internal::panic_message(&*mismatch.unwrap_err());
+        assert!(
+            message.contains("failpoint 'context_test' context:"),
+            "{message}"
+        );
+        assert!(message.contains("hits: 1"), "{message}");
+        assert!(message.contains("action: macro default"), "{message}");
+        assert!(message.contains("fail at src/lib.rs:"), "{message}");
+
+        let mismatch = std::panic::catch_unwind(|| {
+            assert_failpoint_hits!("context_test", 2);
+        });
+        let message = __failpoint_internal::panic_message(&*mismatch.unwrap_err());
+        assert!(
+            message.contains("failpoint 'context_test' context:"),
+            "{message}"
+        );
+        assert!(message.contains("hits: 1"), "{message}");
+    }
+
+    #[test]
+    fn test_maybe_fail_send_and_recv() {
+        use std::sync::mpsc::{RecvError, SendError};
+
+        let (tx, rx) = std::sync::mpsc::channel::<u32>();
+        let send = |item| -> Result<(), SendError<u32>> {
+            maybe_fail_send!("fail_send_test", tx, item, SendError(item))
+        };
+        let recv =
+            || -> Result<u32, RecvError> { maybe_fail_recv!("fail_recv_test", rx, RecvError) };
+
+        send(1).unwrap();
+        __failpoint_internal::enable_failpoint("fail_send_test");
+        assert_eq!(send(2), Err(SendError(2)));
+        __failpoint_internal::disable_failpoint("fail_send_test");
+        send(3).unwrap();
+
+        __failpoint_internal::enable_failpoint("fail_recv_test");
+        assert_eq!(recv(), Err(RecvError));
+        __failpoint_internal::disable_failpoint("fail_recv_test");
+        assert_eq!(recv(), Ok(1));
+        assert_eq!(recv(), Ok(3));
+    }
+
+    #[test]
+    fn test_with_failpoint_error_within() {
+        fn flaky() -> Result<(), ()> {
+            maybe_fail!("error_within_test", ());
+            Ok(())
+        }
+
+        configure_probability("error_within_test", 0.5);
+        with_failpoint!("error_within_test", error_within(50), flaky());
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "error_within_test"
+        ));
+
+        configure_probability("error_within_test", 0.0);
+        let result = std::panic::catch_unwind(|| {
+            with_failpoint!("error_within_test", error_within(5), flaky());
+        });
+        clear_probability("error_within_test");
+        assert!(result.is_err());
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "error_within_test"
+        ));
+    }
+
+    #[test]
+    fn test_enable_failpoint_with_action() {
+        fn fetch() -> Result<(), ()> {
+            maybe_fail!("with_action_variant_test", ());
+            Ok(())
+        }
+        let tag = "with_action_variant_test";
+
+        enable_failpoint_with_action(tag, Action::Error);
+        assert_eq!(fetch(), Err(()));
+        let start = std::time::Instant::now();
+        maybe_sleep!("with_action_variant_test", 200);
+        assert!(start.elapsed() < std::time::Duration::from_millis(100));
+
+        enable_failpoint_with_action(tag, Action::Panic);
+        assert!(std::panic::catch_unwind(fetch).is_err());
+
+        enable_failpoint_with_action(tag, Action::Sleep(std::time::Duration::from_millis(30)));
+        let start = std::time::Instant::now();
+        assert_eq!(fetch(), Ok(()));
+        assert!(start.elapsed() >= std::time::Duration::from_millis(30));
+
+        enable_failpoint_with_action(tag, Action::Return);
+        assert_eq!(fetch(), Ok(()));
+        maybe_panic!("with_action_variant_test");
+
+        __failpoint_internal::disable_failpoint(tag);
+        __failpoint_internal::enable_failpoint(tag);
+        maybe_panic!("with_action_variant_test");
+
+        clear_action(tag);
+        let result = std::panic::catch_unwind(|| {
+            maybe_panic!("with_action_variant_test");
+        });
+        assert!(result.is_err());
+        __failpoint_internal::disable_failpoint(tag);
+    }
+
+    #[test]
+    fn test_enable_disable_failpoints_bulk() {
+        let tags: Vec<&'static str> = (0..100)
+            .map(|i| &*Box::leak(format!("bulk_enable_test_{i}").into_boxed_str()))
+            .collect();
+
+        let updates = || __failpoint_internal::COUNT_UPDATES.with(std::cell::Cell::get);
+        let locks = || __failpoint_internal::RELEASE_LOCKS.with(std::cell::Cell::get);
+
+        let before = updates();
+        enable_failpoints(&tags);
+        assert_eq!(updates(), before + 1);
+        assert!(enabled_failpoint_count() >= tags.len());
+        assert!(
+            tags.iter()
+                .all(|tag| __failpoint_internal::is_failpoint_enabled(tag))
+        );
+
+        let (before, locks_before) = (updates(), locks());
+        disable_failpoints(&tags);
+        assert_eq!(updates(), before + 1);
+        assert_eq!(locks(), locks_before + 1);
+        assert!(
+            !tags
+                .iter()
+                .any(|tag| __failpoint_internal::is_failpoint_enabled(tag))
+        );
+    }
+
+    #[test]
+    fn test_sleep_stats_mean_with_large_count() {
+        let stats = SleepStats {
+            count: 1 << 32,
+            min: std::time::Duration::from_secs(1),
+            max: std::time::Duration::from_secs(1),
+            total: std::time::Duration::from_secs(1 << 32),
+        };
+        assert_eq!(stats.mean(), std::time::Duration::from_secs(1));
+        assert_eq!(SleepStats::default().mean(), std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn test_scoped() {
+        let value = scoped("scoped_test", || {
+            assert!(__failpoint_internal::is_failpoint_enabled("scoped_test"));
+            7
+        });
+
+        assert_eq!(value, 7);
+        assert!(!__failpoint_internal::is_failpoint_enabled("scoped_test"));
+    }
+
+    #[test]
+    fn test_scoped_cleanup_on_panic() {
+        let result = std::panic::catch_unwind(|| {
+            scoped("scoped_panic_test", || panic!("boom"));
+        });
+
+        assert!(result.is_err());
+        assert!(!__failpoint_internal::is_failpoint_enabled(
+            "scoped_panic_test"
+        ));
+    }
 }
@@ -1,5 +1,51 @@
+/// Evaluates to whether a macro that returns an error should fail at `tag`: the tag fires
+/// and its configured actions don't stop at `ChainOutcome::Proceed`. Not public API.
+///
+/// The tag is registered before anything else. `if gate` is checked next, so evaluations
+/// the calling macro's own condition rejects are neither hits nor injections; `then check`
+/// is checked once the tag has fired, before its actions run.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __chaos_should_fail {
+    (async $tag:literal, $action:literal) => {
+        $crate::__failpoint_internal::should_fire($tag, $action)
+            && $crate::__failpoint_internal::execute_chain_async($tag).await
+                != $crate::__failpoint_internal::ChainOutcome::Proceed
+    };
+    ($tag:literal, $action:literal $(, if $gate:expr)? $(, then $check:expr)?) => {{
+        $crate::__failpoint_internal::register_tag($tag);
+        $($gate &&)? $crate::__failpoint_internal::should_fire_registered($tag, $action)
+            $(&& $check)?
+            && $crate::__failpoint_internal::execute_chain($tag)
+                != $crate::__failpoint_internal::ChainOutcome::Proceed
+    }};
+}
+
+/// Evaluates to whether a macro whose injection isn't an error, such as a sleep or a
+/// panic, should perform it at `tag`: the tag fires and has no configured action. When it
+/// has one, the actions run here in place of the macro's own. Not public API.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __chaos_should_act {
+    (async $tag:literal, $action:literal) => {
+        $crate::__failpoint_internal::should_fire($tag, $action)
+            && $crate::__failpoint_internal::execute_chain_async($tag).await
+                == $crate::__failpoint_internal::ChainOutcome::Unconfigured
+    };
+    ($tag:literal, $action:literal) => {
+        $crate::__failpoint_internal::should_fire($tag, $action)
+            && $crate::__failpoint_internal::execute_chain($tag)
+                == $crate::__failpoint_internal::ChainOutcome::Unconfigured
+    };
+}
+
 /// Returns `Err(tag.into())` or a custom error when the failpoint is enabled.
 ///
+/// If the tag has an action configured, for example with `enable_failpoint_with_action`, it
+/// runs instead and the error is returned only if it reaches `Action::Error`. The other
+/// injection macros consult the configured action the same way; see [`Action`](crate::Action)
+/// for how each kind of macro reacts to it.
+///
 /// # Examples
 /// ```rust
 /// fn perform_action() -> Result<&'static str, String> {
@@ -20,7 +66,7 @@ macro_rules! maybe_fail {
     ($tag:literal) => {
         #[cfg(feature = "chaos")]
         {
-            if $crate::__failpoint_internal::is_failpoint_enabled($tag) {
+            if $crate::__chaos_should_fail!($tag, "fail") {
                 return Err($tag.into());
             }
         }
@@ -28,164 +74,2043 @@ macro_rules! maybe_fail {
     ($tag:literal, $err:expr) => {
         #[cfg(feature = "chaos")]
         {
-            if $crate::__failpoint_internal::is_failpoint_enabled($tag) {
+            if $crate::__chaos_should_fail!($tag, "fail") {
                 return Err($err);
             }
         }
     };
 }
 
-/// Panics when the failpoint is enabled.
+/// Wraps a closure so that it returns `Err(tag.into())` instead of running its body while
+/// the failpoint is enabled.
+///
+/// Use it for callbacks handed to other code, such as `map` or a library's hooks. The
+/// closure must return a `Result` whose error type implements `From<&'static str>`, like
+/// with `maybe_fail!`. Parameters must be plain identifiers, optionally with a type
+/// annotation, and a leading `move` is kept.
+/// The failpoint is evaluated on every call, not when the closure is created.
 ///
 /// # Example
 /// ```rust
-/// fn critical() {
-///     chaos_rs::maybe_panic!("unexpected_panic");
+/// let doubled: Result<Vec<u32>, String> = [1, 2, 3]
+///     .into_iter()
+///     .map(chaos_rs::chaos_closure!("map_callback", |x: u32| Ok(x * 2)))
+///     .collect();
+/// assert_eq!(doubled.unwrap(), [2, 4, 6]);
+/// ```
+#[macro_export]
+macro_rules! chaos_closure {
+    ($tag:literal, move |$($arg:ident $(: $ty:ty)?),* $(,)?| $body:expr) => {
+        move |$($arg $(: $ty)?),*| {
+            $crate::maybe_fail!($tag);
+            $body
+        }
+    };
+    ($tag:literal, |$($arg:ident $(: $ty:ty)?),* $(,)?| $body:expr) => {
+        |$($arg $(: $ty)?),*| {
+            $crate::maybe_fail!($tag);
+            $body
+        }
+    };
+}
+
+/// Returns `Err(err)` from a read path when the failpoint is enabled for reads.
+///
+/// Shares the tag with `maybe_fail_write!`: a plain `enable_failpoint` impairs both, while
+/// `enable_failpoint_direction(tag, Direction::Read)` leaves writes working.
+///
+/// # Example
+/// ```rust
+/// fn read_page() -> Result<Vec<u8>, String> {
+///     chaos_rs::maybe_fail_read!("storage", "read failed".to_string());
+///     Ok(vec![0; 4096])
 /// }
 /// ```
 #[macro_export]
-macro_rules! maybe_panic {
-    ($tag:literal) => {
+macro_rules! maybe_fail_read {
+    ($tag:literal, $err:expr) => {
         #[cfg(feature = "chaos")]
         {
-            if $crate::__failpoint_internal::is_failpoint_enabled($tag) {
-                panic!($tag);
+            if $crate::__chaos_should_fail!(
+                $tag,
+                "fail read",
+                if $crate::__failpoint_internal::direction_allows($tag, $crate::Direction::Read)
+            ) {
+                return Err($err);
             }
         }
     };
 }
 
-/// Sleeps for a given number of milliseconds when the failpoint is enabled.
+/// Returns `Err(err)` from a write path when the failpoint is enabled for writes.
+///
+/// The write-side counterpart of `maybe_fail_read!`.
 ///
 /// # Example
 /// ```rust
-/// chaos_rs::maybe_sleep!("slow_io", 500);
+/// fn write_page(_page: &[u8]) -> Result<(), String> {
+///     chaos_rs::maybe_fail_write!("storage", "write failed".to_string());
+///     Ok(())
+/// }
 /// ```
 #[macro_export]
-macro_rules! maybe_sleep {
-    ($tag:literal, $millis:literal) => {
+macro_rules! maybe_fail_write {
+    ($tag:literal, $err:expr) => {
         #[cfg(feature = "chaos")]
         {
-            if $crate::__failpoint_internal::is_failpoint_enabled($tag) {
-                std::thread::sleep(std::time::Duration::from_millis($millis));
+            if $crate::__chaos_should_fail!(
+                $tag,
+                "fail write",
+                if $crate::__failpoint_internal::direction_allows($tag, $crate::Direction::Write)
+            ) {
+                return Err($err);
             }
         }
     };
 }
 
-/// If the specified failpoint is enabled, this macro will pause the asynchronous
-/// execution for a given number of milliseconds.
+/// Drops `guard` and then returns `Err(err)` when the failpoint is enabled.
+///
+/// Simulates a failure at a specific point inside a critical section. `guard` (typically
+/// a `MutexGuard` or `RwLock` guard variable) is moved into the macro, but only on the
+/// failing path: when the failpoint is disabled the guard stays usable afterwards. The lock
+/// is released cleanly before returning, so it is not poisoned. `err` is evaluated after
+/// the guard is dropped.
 ///
 /// # Example
 /// ```rust
-/// chaos_rs::maybe_sleep_async!("slow_io", 500);
+/// use std::sync::Mutex;
+///
+/// fn update(balance: &Mutex<u64>) -> Result<(), String> {
+///     let mut guard = balance.lock().unwrap();
+///     *guard += 10;
+///     chaos_rs::maybe_fail_after_unlock!("mid_update", guard, "crashed mid-update".into());
+///     *guard -= 1;
+///     Ok(())
+/// }
 /// ```
 #[macro_export]
-macro_rules! maybe_sleep_async {
-    ($tag:literal, $millis:literal) => {
+macro_rules! maybe_fail_after_unlock {
+    ($tag:literal, $guard:ident, $err:expr) => {
         #[cfg(feature = "chaos")]
         {
-            if $crate::__failpoint_internal::is_failpoint_enabled($tag) {
-                let duration = std::time::Duration::from_millis($millis);
-                $crate::__failpoint_internal::sleep_async_internal(duration).await;
+            if $crate::__chaos_should_fail!($tag, "fail after unlock") {
+                drop($guard);
+                return Err($err);
             }
         }
     };
 }
 
-/// Runs a code block with a failpoint enabled and validates its effect.
+/// Returns `Err(Box<dyn Error + Send + Sync>)` carrying the tag when the failpoint is enabled.
 ///
-/// Supported modes:
-/// - `panic`: Expects the code to panic when the failpoint is active.
-/// - `error`: Expects the code to return `Err` when the failpoint is active.
-/// - Sleep validation: Verifies that code sleeps somewhere in the range of `min_ms` - `tolerance` and `min_ms` + `tolerance` when failpoint is active.
+/// For functions returning `Result<T, Box<dyn std::error::Error + Send + Sync>>` (or plain
+/// `Box<dyn Error>`, which it coerces to), where `maybe_fail!`'s `tag.into()` doesn't
+/// pick a boxed error.
 ///
-/// # Examples
+/// # Example
+/// ```rust
+/// use std::error::Error;
 ///
-/// Expects a panic:
+/// fn load() -> Result<String, Box<dyn Error>> {
+///     chaos_rs::maybe_box_fail!("load_config");
+///     Ok("config".into())
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_box_fail {
+    ($tag:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!($tag, "fail") {
+                return Err(Box::<dyn std::error::Error + Send + Sync>::from($tag));
+            }
+        }
+    };
+}
+
+/// Returns `Err(err)` when the failpoint is enabled and the environment variable `var` is
+/// set to a truthy value.
+///
+/// Any value but empty, `0`, `false`, `no` or `off` (case-insensitive) counts as truthy.
+/// The variable is read each time the macro is evaluated, so chaos can be switched on and
+/// off by changing it while the process runs. Hits are only counted when the variable
+/// allows the failure.
+///
+/// # Example
 /// ```rust
-/// chaos_rs::with_failpoint!("panic_test", panic, {
-///     chaos_rs::maybe_panic!("panic_test");
-/// });
+/// fn send() -> Result<(), String> {
+///     chaos_rs::maybe_fail_env!("send_fail", "MY_CHAOS", "send failed".to_string());
+///     Ok(())
+/// }
 /// ```
+#[macro_export]
+macro_rules! maybe_fail_env {
+    ($tag:literal, $var:literal, $err:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!(
+                $tag,
+                "fail",
+                if $crate::__failpoint_internal::is_failpoint_enabled($tag)
+                    && $crate::__failpoint_internal::env_allows($var)
+            ) {
+                return Err($err);
+            }
+        }
+    };
+}
+
+/// Returns `Err(err)` when the failpoint is enabled and the current generation equals
+/// `target_gen`.
 ///
-/// Expects an error:
+/// The generation is a process-wide counter set with
+/// [`set_generation`](crate::set_generation), so a test can enable its tags once and move
+/// the failure between phases by advancing it. Hits are only counted in the target
+/// generation.
+///
+/// # Example
 /// ```rust
-/// chaos_rs::with_failpoint!("error_test", error, {
-///     fn test() -> Result<(), ()> {
-///         chaos_rs::maybe_fail!("error_test", ());
-///         Ok(())
-///     }
-///     test()
-/// });
+/// fn replicate() -> Result<(), String> {
+///     chaos_rs::maybe_fail_gen!("replicate", 2, "replica lost".to_string());
+///     Ok(())
+/// }
 /// ```
+#[macro_export]
+macro_rules! maybe_fail_gen {
+    ($tag:literal, $target_gen:expr, $err:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!(
+                $tag,
+                "fail",
+                if $crate::__failpoint_internal::generation() == $target_gen
+            ) {
+                return Err($err);
+            }
+        }
+    };
+}
+
+/// Returns `Err(err)` for a stable `sample_rate` fraction of keys when the failpoint is
+/// enabled.
 ///
-/// Expects the operation to sleep for 200 ± 50ms (150 - 250 range):
+/// `key` is any `Hash` value identifying an entity, such as a user or shard id. It is
+/// hashed together with the tag and selected if the hash falls below `sample_rate`, so the
+/// same key always gets the same decision while the tag stays enabled, and roughly
+/// `sample_rate` of distinct keys fail. The hash is fixed and doesn't depend on `set_seed`.
+/// Hits are only counted for selected keys.
+///
+/// # Example
 /// ```rust
-/// chaos_rs::with_failpoint!("sleep_test", 200, 50, {
-///     chaos_rs::maybe_sleep!("sleep_test", 200);
-/// });
+/// fn load_user(id: u64) -> Result<String, String> {
+///     chaos_rs::maybe_fail_hashed!("user_shard", id, 0.1, "shard offline".to_string());
+///     Ok(format!("user {id}"))
+/// }
 /// ```
 #[macro_export]
-macro_rules! with_failpoint {
-    ($tag:literal, panic, $code:expr) => {{
+macro_rules! maybe_fail_hashed {
+    ($tag:literal, $key:expr, $sample_rate:expr, $err:expr) => {
         #[cfg(feature = "chaos")]
         {
-            $crate::__failpoint_internal::enable_failpoint($tag);
-            let result = std::panic::catch_unwind(|| $code);
-            $crate::__failpoint_internal::disable_failpoint($tag);
-            match result {
-                Ok(_) => panic!(
-                    "Expected panic from failpoint '{}', but none occurred",
-                    $tag
-                ),
-                Err(_) => {}
+            if $crate::__chaos_should_fail!(
+                $tag,
+                "fail",
+                if $crate::__failpoint_internal::is_failpoint_enabled($tag)
+                    && $crate::__failpoint_internal::hashed_allows($tag, &$key, $sample_rate)
+            ) {
+                return Err($err);
             }
         }
+    };
+}
+
+/// Evaluates to the result of sending `item` on a `std::sync::mpsc` sender, or to
+/// `Err(err)` without sending when the failpoint is enabled.
+///
+/// Works with `Sender` and `SyncSender`. The macro evaluates to `Result<(), E>`, where `E`
+/// is the type of `err`; a real `SendError<T>` is converted with `E::from`, so `E` can be
+/// `SendError<T>` itself or an application error that wraps it. An injected failure drops
+/// `item`. For `tokio::sync::mpsc` use `maybe_fail_send_async!`.
+///
+/// # Example
+/// ```rust
+/// use std::sync::mpsc::{SendError, channel};
+///
+/// let (tx, rx) = channel::<u32>();
+/// let sent: Result<(), SendError<u32>> =
+///     chaos_rs::maybe_fail_send!("queue_send", tx, 7, SendError(0));
+/// sent.unwrap();
+/// assert_eq!(rx.recv().unwrap(), 7);
+/// ```
+#[macro_export]
+macro_rules! maybe_fail_send {
+    ($tag:literal, $tx:expr, $item:expr, $err:expr) => {{
+        #[cfg(feature = "chaos")]
+        let fired = $crate::__chaos_should_fail!($tag, "fail send");
+        #[cfg(not(feature = "chaos"))]
+        let fired = false;
+        match fired {
+            true => Err($err),
+            false => $tx.send($item).map_err(::std::convert::From::from),
+        }
     }};
+}
 
-    ($tag:literal, error, $code:expr) => {{
+/// Evaluates to the result of receiving from a `std::sync::mpsc` receiver, or to
+/// `Err(err)` without receiving when the failpoint is enabled.
+///
+/// The macro evaluates to `Result<T, E>`, where `E` is the type of `err`; a real
+/// `RecvError` is converted with `E::from`. The message that would have been received
+/// stays queued for the next call. For `tokio::sync::mpsc` use `maybe_fail_recv_async!`.
+///
+/// # Example
+/// ```rust
+/// use std::sync::mpsc::{RecvError, channel};
+///
+/// let (tx, rx) = channel::<u32>();
+/// tx.send(7).unwrap();
+/// let received: Result<u32, RecvError> = chaos_rs::maybe_fail_recv!("queue_recv", rx, RecvError);
+/// assert_eq!(received.unwrap(), 7);
+/// ```
+#[macro_export]
+macro_rules! maybe_fail_recv {
+    ($tag:literal, $rx:expr, $err:expr) => {{
         #[cfg(feature = "chaos")]
-        {
-            $crate::__failpoint_internal::enable_failpoint($tag);
-            let result = $code;
-            $crate::__failpoint_internal::disable_failpoint($tag);
+        let fired = $crate::__chaos_should_fail!($tag, "fail recv");
+        #[cfg(not(feature = "chaos"))]
+        let fired = false;
+        match fired {
+            true => Err($err),
+            false => $rx.recv().map_err(::std::convert::From::from),
+        }
+    }};
+}
 
-            match result {
-                Err(_) => {}
-                Ok(_) => panic!(
-                    "Expected error from failpoint '{}', but function returned Ok",
-                    $tag
-                ),
-            }
+/// Async counterpart of `maybe_fail_send!` for `tokio::sync::mpsc` senders.
+///
+/// Requires the `tokio` feature. Evaluates to `Result<(), E>` like `maybe_fail_send!`,
+/// awaiting `tx.send(item)` when the failpoint doesn't fire and converting its
+/// `SendError<T>` with `E::from`.
+///
+/// # Example
+/// ```rust
+/// use tokio::sync::mpsc::{channel, error::SendError};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let (tx, mut rx) = channel::<u32>(1);
+/// let sent: Result<(), SendError<u32>> =
+///     chaos_rs::maybe_fail_send_async!("queue_send_async", tx, 7, SendError(0));
+/// sent.unwrap();
+/// assert_eq!(rx.recv().await, Some(7));
+/// # });
+/// ```
+#[cfg(feature = "tokio")]
+#[macro_export]
+macro_rules! maybe_fail_send_async {
+    ($tag:literal, $tx:expr, $item:expr, $err:expr) => {{
+        #[cfg(feature = "chaos")]
+        let fired = $crate::__chaos_should_fail!(async $tag, "fail send");
+        #[cfg(not(feature = "chaos"))]
+        let fired = false;
+        match fired {
+            true => Err($err),
+            false => $tx.send($item).await.map_err(::std::convert::From::from),
         }
     }};
+}
 
-    ($tag:literal, $min_ms:literal, $tolerance_ms:literal, $code:expr) => {{
+/// Async counterpart of `maybe_fail_recv!` for `tokio::sync::mpsc` receivers.
+///
+/// Requires the `tokio` feature. Tokio's `recv` reports a closed channel as `None` rather
+/// than an error, so the macro evaluates to `Result<Option<T>, E>`: `Err(err)` when the
+/// failpoint fires, and `Ok` with whatever `rx.recv().await` returned otherwise.
+///
+/// # Example
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let (tx, mut rx) = tokio::sync::mpsc::channel::<u32>(1);
+/// tx.send(7).await.unwrap();
+/// let received: Result<Option<u32>, String> =
+///     chaos_rs::maybe_fail_recv_async!("queue_recv_async", rx, "injected".into());
+/// assert_eq!(received.unwrap(), Some(7));
+/// # });
+/// ```
+#[cfg(feature = "tokio")]
+#[macro_export]
+macro_rules! maybe_fail_recv_async {
+    ($tag:literal, $rx:expr, $err:expr) => {{
+        #[cfg(feature = "chaos")]
+        let fired = $crate::__chaos_should_fail!(async $tag, "fail recv");
+        #[cfg(not(feature = "chaos"))]
+        let fired = false;
+        match fired {
+            true => Err($err),
+            false => Ok($rx.recv().await),
+        }
+    }};
+}
+
+/// Returns `Err(err)` if any of the listed failpoints is enabled.
+///
+/// Tags are evaluated left to right and evaluation stops at the first one that fires, so
+/// later tags are not counted as hit.
+///
+/// # Example
+/// ```rust
+/// fn sync_replica() -> Result<(), String> {
+///     chaos_rs::maybe_fail_any!(["replica_down", "network_partition"], "unreachable".into());
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_fail_any {
+    ([$($tag:literal),+ $(,)?], $err:expr) => {
         #[cfg(feature = "chaos")]
         {
-            $crate::__failpoint_internal::enable_failpoint($tag);
-            let start = std::time::Instant::now();
-            $code;
-            let elapsed = start.elapsed();
-            $crate::__failpoint_internal::disable_failpoint($tag);
+            if $($crate::__chaos_should_fail!($tag, "fail"))||+ {
+                return Err($err);
+            }
+        }
+    };
+}
 
-            let max = std::time::Duration::from_millis($min_ms + $tolerance_ms);
-            let min = std::time::Duration::from_millis($min_ms - $tolerance_ms);
+/// Returns each error from a list in turn on successive hits of the failpoint.
+///
+/// The first hit returns the first error, the second hit the second, and so on, wrapping
+/// around after the last one. The position comes from the tag's hit counter, so
+/// `reset_failpoint_count` starts the cycle over.
+///
+/// # Example
+/// ```rust
+/// #[derive(Debug)]
+/// enum DbError {
+///     Timeout,
+///     Refused,
+/// }
+///
+/// fn query() -> Result<u32, DbError> {
+///     chaos_rs::maybe_fail_cycle!("db_cycle", [DbError::Timeout, DbError::Refused]);
+///     Ok(1)
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_fail_cycle {
+    ($tag:literal, [$($err:expr),+ $(,)?]) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!($tag, "fail") {
+                let errors = [$($err),+];
+                let hits = $crate::__failpoint_internal::failpoint_hits($tag).max(1);
+                let index = ((hits - 1) % errors.len() as u64) as usize;
+                return Err(errors.into_iter().nth(index).unwrap());
+            }
+        }
+    };
+}
 
-            assert!(
-                elapsed <= max && elapsed >= min,
-                "Expected sleep between {:?} and {:?} from failpoint '{}', got {:?}",
-                min,
-                max,
+/// Alternates between returning `Err(err)` and proceeding on successive hits, simulating a
+/// flapping dependency.
+///
+/// The alternation follows the tag's hit counter and starts failing: the first hit after
+/// enabling (or after `reset_failpoint_count`) returns the error, the second proceeds, the
+/// third fails again, and so on.
+///
+/// # Example
+/// ```rust
+/// fn ping() -> Result<(), String> {
+///     chaos_rs::maybe_flap!("flaky_upstream", "upstream unavailable".to_string());
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_flap {
+    ($tag:literal, $err:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!(
                 $tag,
-                elapsed
-            );
+                "flap",
+                then $crate::__failpoint_internal::failpoint_hits($tag) % 2 == 1
+            ) {
+                return Err($err);
+            }
         }
-    }};
+    };
 }
+
+/// Returns `Err(err)` on the first `n` hits after the failpoint is enabled, then heals.
+///
+/// The failpoint disables itself on its `n`th failure, so later calls proceed normally
+/// until it is enabled again, which starts a fresh count. With `n` of zero it never fails
+/// and no hits are counted. This models a dependency that recovers after a few failed
+/// attempts, for exercising retry loops; see `assert_eventually_succeeds!`.
+///
+/// # Example
+/// ```rust
+/// fn connect() -> Result<(), String> {
+///     chaos_rs::maybe_fail_times!("warming_up", 3, "not ready".to_string());
+///     Ok(())
+/// }
+/// ```
 #[macro_export]
-macro_rules! with_failpoint_async {
+macro_rules! maybe_fail_times {
+    ($tag:literal, $n:expr, $err:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            let times: u64 = $n;
+            if $crate::__chaos_should_fail!(
+                $tag,
+                "fail",
+                if times != 0,
+                then $crate::__failpoint_internal::fail_times_allows($tag, times)
+            ) {
+                return Err($err);
+            }
+        }
+    };
+}
+
+/// Returns `Poll::Ready(Err(err))` from a `Future::poll` on the `n`th poll while the
+/// failpoint is enabled.
+///
+/// Each tag keeps its own poll counter: every evaluation while the tag is enabled counts
+/// as one poll, starting from 1, and the counter restarts when the tag is disabled. Only
+/// the `n`th poll fails, so later polls proceed normally.
+///
+/// # Example
+/// ```rust
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll};
+///
+/// struct Handshake;
+///
+/// impl Future for Handshake {
+///     type Output = Result<(), String>;
+///
+///     fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+///         chaos_rs::maybe_fail_on_poll!("handshake", 2, "reset mid-handshake".to_string());
+///         Poll::Ready(Ok(()))
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_fail_on_poll {
+    ($tag:literal, $n:expr, $err:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!(
+                $tag,
+                "fail on poll",
+                if $crate::__failpoint_internal::next_poll($tag) == $n
+            ) {
+                return std::task::Poll::Ready(Err($err));
+            }
+        }
+    };
+}
+
+/// Records an injected failure for the current thread instead of failing, for use in `Drop`.
+///
+/// Evaluates to `true` when the failpoint fires so the drop path can take its failure
+/// branch, and records the tag; [`take_drop_failures`](crate::take_drop_failures) returns
+/// the tags recorded on the thread once the value is gone. It never panics or returns:
+/// `drop` can't return an error, and panicking there while already unwinding aborts the
+/// process. For the same reason it ignores any action configured for the tag.
+///
+/// # Example
+/// ```rust
+/// struct Connection;
+///
+/// impl Drop for Connection {
+///     fn drop(&mut self) {
+///         if chaos_rs::maybe_fail_in_drop!("close_conn") {
+///             return; // skip the graceful close, as if it failed
+///         }
+///         // send a close frame...
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_fail_in_drop {
+    ($tag:literal) => {{
+        #[cfg(not(feature = "chaos"))]
+        let fired = false;
+        #[cfg(feature = "chaos")]
+        let fired = $crate::__failpoint_internal::should_fire($tag, "fail in drop");
+        #[cfg(feature = "chaos")]
+        if fired {
+            $crate::__failpoint_internal::record_drop_failure($tag);
+        }
+        fired
+    }};
+}
+
+/// Returns a serde error from a manual `Serialize` or `Deserialize` impl when the
+/// failpoint is enabled.
+///
+/// Requires the `serde` feature. Inside `serialize` use `maybe_serde_fail!("tag")`, which
+/// returns `S::Error::custom("injected serialization failure at '<tag>'")`; inside
+/// `deserialize` use `maybe_serde_fail!("tag", deserialize)`, which returns
+/// `D::Error::custom("injected deserialization failure at '<tag>'")`. The error is the
+/// format's own error type, so callers see it exactly like malformed data.
+///
+/// # Example
+/// ```rust
+/// use serde::{Serialize, Serializer};
+///
+/// struct Reading(f64);
+///
+/// impl Serialize for Reading {
+///     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+///         chaos_rs::maybe_serde_fail!("encode_reading");
+///         serializer.serialize_f64(self.0)
+///     }
+/// }
+/// ```
+#[cfg(feature = "serde")]
+#[macro_export]
+macro_rules! maybe_serde_fail {
+    ($tag:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!($tag, "serde fail") {
+                return Err($crate::__failpoint_internal::serde_ser_error($tag));
+            }
+        }
+    };
+    ($tag:literal, deserialize) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!($tag, "serde fail") {
+                return Err($crate::__failpoint_internal::serde_de_error($tag));
+            }
+        }
+    };
+}
+
+/// Declares failpoint tags so [`known_failpoints`](crate::known_failpoints) lists them
+/// before any of them is evaluated.
+///
+/// Requires the `inventory` feature. Each tag is submitted to a link-time registry built
+/// with the `inventory` crate: the linker gathers the declarations from every crate in the
+/// binary, and they're added to the known tags the first time the list is read. Use it at
+/// module level, next to the code that evaluates the tags, so documentation and control
+/// UIs can discover every injection point statically. Declaring a tag doesn't enable it.
+///
+/// # Example
+/// ```rust
+/// chaos_rs::define_failpoints!("db_connect", "db_query");
+///
+/// # #[cfg(feature = "chaos")]
+/// assert!(chaos_rs::known_failpoints().contains(&"db_query".to_string()));
+/// ```
+#[cfg(feature = "inventory")]
+#[macro_export]
+macro_rules! define_failpoints {
+    ($($tag:literal),+ $(,)?) => {
+        $(
+            #[cfg(feature = "chaos")]
+            $crate::__failpoint_internal::inventory::submit! {
+                $crate::__failpoint_internal::StaticFailpoint { tag: $tag }
+            }
+        )+
+    };
+}
+
+/// Fails compilation if the `chaos` feature is enabled in a release build.
+///
+/// Place it once in a crate that gates chaos behind its own `chaos` feature; builds without
+/// `debug_assertions` then refuse to compile with chaos enabled, so it can't ship to
+/// production by accident. It expands to nothing otherwise.
+///
+/// To run chaos in production on purpose, gate the invocation behind your own opt-out, e.g.
+/// `#[cfg(not(feature = "production-chaos"))]`, or simply don't invoke the macro.
+///
+/// # Example
+/// ```rust
+/// chaos_rs::assert_chaos_disabled_in_release!();
+/// ```
+#[macro_export]
+macro_rules! assert_chaos_disabled_in_release {
+    () => {
+        #[cfg(all(feature = "chaos", not(debug_assertions)))]
+        compile_error!(
+            "the `chaos` feature is enabled in a release build; disable it or opt out of \
+             `assert_chaos_disabled_in_release!`"
+        );
+    };
+}
+
+/// Returns `Err(std::io::Error)` of the given kind when the failpoint is enabled.
+///
+/// Defaults to `std::io::ErrorKind::Other` when no kind is given.
+///
+/// # Examples
+/// ```rust
+/// fn read_block() -> std::io::Result<Vec<u8>> {
+///     chaos_rs::maybe_io_fail!("disk_read");
+///     Ok(vec![0; 512])
+/// }
+/// ```
+///
+/// With a specific kind:
+/// ```rust
+/// use std::io::ErrorKind;
+///
+/// fn connect() -> std::io::Result<()> {
+///     chaos_rs::maybe_io_fail!("connect_timeout", ErrorKind::TimedOut);
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_io_fail {
+    ($tag:literal) => {
+        $crate::maybe_io_fail!($tag, std::io::ErrorKind::Other)
+    };
+    ($tag:literal, $kind:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!($tag, "fail") {
+                return Err(std::io::Error::from($kind));
+            }
+        }
+    };
+}
+
+/// Returns `Err(AllocError)` when the failpoint is enabled, simulating an allocation failure.
+///
+/// Rust aborts on a real allocation failure, so this doesn't exhaust memory at all: it
+/// returns an [`AllocError`](crate::AllocError) that the caller converts with `.into()`,
+/// letting fallible-allocation paths (e.g. around `try_reserve`) be exercised safely.
+///
+/// # Example
+/// ```rust
+/// use chaos_rs::AllocError;
+///
+/// fn grow(buf: &mut Vec<u8>) -> Result<(), AllocError> {
+///     chaos_rs::maybe_alloc_fail!("grow_buffer");
+///     buf.try_reserve(4096).map_err(|_| AllocError::new("grow_buffer"))
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_alloc_fail {
+    ($tag:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!($tag, "alloc fail") {
+                return Err($crate::AllocError::new($tag).into());
+            }
+        }
+    };
+}
+
+/// Returns `Err(CodedError)` with the given numeric code when the failpoint is enabled.
+///
+/// The [`CodedError`](crate::CodedError) is converted with `.into()`, so functions can
+/// return it directly or through their own error type, and tests assert on
+/// [`code`](crate::CodedError::code) rather than a message.
+///
+/// # Example
+/// ```rust
+/// use chaos_rs::CodedError;
+///
+/// fn fetch() -> Result<Vec<u8>, CodedError> {
+///     chaos_rs::maybe_fail_code!("fetch_status", 503);
+///     Ok(Vec::new())
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_fail_code {
+    ($tag:literal, $code:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!($tag, "fail") {
+                return Err($crate::CodedError::new($tag, $code).into());
+            }
+        }
+    };
+}
+
+/// Returns `Err(HttpError)` with the given `http::StatusCode` when the failpoint is enabled.
+///
+/// Requires the `http` feature. The [`HttpError`](crate::HttpError) is converted with
+/// `.into()` and exposes the status, so HTTP client code can exercise its retry and backoff
+/// paths without a server; use [`HttpError::response`](crate::HttpError::response) where a
+/// response value is expected instead.
+///
+/// # Example
+/// ```rust
+/// use chaos_rs::HttpError;
+/// use http::StatusCode;
+///
+/// fn get() -> Result<String, HttpError> {
+///     chaos_rs::maybe_http_fail!("api_get", StatusCode::SERVICE_UNAVAILABLE);
+///     Ok("body".into())
+/// }
+/// ```
+#[cfg(feature = "http")]
+#[macro_export]
+macro_rules! maybe_http_fail {
+    ($tag:literal, $status:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!($tag, "http fail") {
+                return Err($crate::HttpError::new($tag, $status).into());
+            }
+        }
+    };
+}
+
+/// Runs `effect` when the failpoint is enabled, then lets the function continue normally.
+///
+/// Models soft failures such as a dropped cache or a skipped metric: nothing is returned
+/// early, so the surrounding code still runs to completion.
+///
+/// # Example
+/// ```rust
+/// fn render() -> Result<String, String> {
+///     let mut cache_hit = true;
+///     chaos_rs::maybe_degrade!("cache_miss", || cache_hit = false);
+///     Ok(format!("cache hit: {}", cache_hit))
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_degrade {
+    ($tag:literal, $effect:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!($tag, "degrade") {
+                ($effect)();
+            }
+        }
+    };
+}
+
+/// Reports a short write by storing less than `buf.len()` into `written` when the failpoint
+/// is enabled.
+///
+/// When the failpoint is disabled `written` is set to the full length of `buf`, so the
+/// caller writes everything as usual. When enabled, the reported length is always shorter
+/// than the buffer (possibly zero) and uses the fraction set by `configure_write_fraction`,
+/// or a random one if none is configured.
+///
+/// # Example
+/// ```rust
+/// use std::io::Write;
+///
+/// struct Flaky<W>(W);
+///
+/// impl<W: Write> Write for Flaky<W> {
+///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+///         let mut written = 0;
+///         chaos_rs::maybe_short_write!("short_write", buf, &mut written);
+///         self.0.write(&buf[..written])
+///     }
+///
+///     fn flush(&mut self) -> std::io::Result<()> {
+///         self.0.flush()
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_short_write {
+    ($tag:literal, $buf:expr, $written:expr) => {{
+        let len = $buf.len();
+        *$written = len;
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!($tag, "short write") {
+                *$written = $crate::__failpoint_internal::short_write_len($tag, len);
+            }
+        }
+    }};
+}
+
+/// Evaluates to a truncated prefix of a read buffer when the failpoint is enabled, so the
+/// read that fills it comes back short.
+///
+/// Pass the `&mut [u8]` handed to `Read::read` and read into the returned slice instead:
+/// the inner reader can then fill at most the prefix, and its reported length is the short
+/// read. With an explicit `n` the prefix is `n` bytes (capped at the buffer length);
+/// without one a random length is drawn on each fire that is at least one byte, so the
+/// short read isn't mistaken for end of file, and shorter than the buffer. When the
+/// failpoint is disabled the whole buffer is returned and the read is unchanged.
+///
+/// # Example
+/// ```rust
+/// use std::io::Read;
+///
+/// struct Choppy<R>(R);
+///
+/// impl<R: Read> Read for Choppy<R> {
+///     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+///         let buf = chaos_rs::maybe_truncate_read!("choppy_read", buf);
+///         self.0.read(buf)
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_truncate_read {
+    ($tag:literal, $buf:expr) => {{
+        let buf: &mut [u8] = $buf;
+        #[cfg(feature = "chaos")]
+        let buf = match $crate::__chaos_should_act!($tag, "truncate read") {
+            true => {
+                let len = $crate::__failpoint_internal::truncated_read_len(buf.len());
+                &mut buf[..len]
+            }
+            false => buf,
+        };
+        buf
+    }};
+    ($tag:literal, $buf:expr, $n:expr) => {{
+        let buf: &mut [u8] = $buf;
+        #[cfg(feature = "chaos")]
+        let buf = match $crate::__chaos_should_act!($tag, "truncate read") {
+            true => {
+                let len = usize::min($n, buf.len());
+                &mut buf[..len]
+            }
+            false => buf,
+        };
+        buf
+    }};
+}
+
+/// Simulates a batch that partially succeeded before failing.
+///
+/// When the failpoint is enabled, `results` (a `&mut Vec<T>`) is truncated to a random
+/// prefix that is shorter than the batch, and the macro evaluates to
+/// `Some((dropped, err))`, where `dropped` is how many results were discarded. When it is
+/// disabled the results are left complete and the macro evaluates to `None`; `err` is only
+/// evaluated when the failpoint fires.
+///
+/// # Example
+/// ```rust
+/// fn commit(batch: &[u32]) -> Result<Vec<u32>, (usize, String)> {
+///     let mut committed: Vec<u32> = batch.to_vec();
+///     if let Some(failure) =
+///         chaos_rs::maybe_partial_fail!("batch_commit", &mut committed, "disk full".to_string())
+///     {
+///         return Err(failure);
+///     }
+///     Ok(committed)
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_partial_fail {
+    ($tag:literal, $results:expr, $err:expr) => {{
+        let results: &mut Vec<_> = $results;
+        let mut failure = None;
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_fail!($tag, "partial fail") {
+                let len = results.len();
+                results.truncate($crate::__failpoint_internal::partial_prefix_len(len));
+                failure = Some((len - results.len(), $err));
+            }
+        }
+        let _ = &results;
+        failure
+    }};
+}
+
+/// Delivers `item` through `deliver` twice instead of once when the failpoint is enabled,
+/// simulating duplicate delivery in at-least-once messaging.
+///
+/// `deliver` is a closure (or function) taking the item by value; it's called once with
+/// `item` normally, and when the failpoint fires first with a clone and then with the
+/// original, so `item` must implement `Clone`. The macro evaluates to what the last call
+/// returned, so send errors stay visible to the caller.
+///
+/// # Example
+/// ```rust
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// chaos_rs::maybe_duplicate!("dup_delivery", "order-17", |m| tx.send(m)).unwrap();
+/// assert_eq!(rx.try_iter().count(), 1);
+/// ```
+#[macro_export]
+macro_rules! maybe_duplicate {
+    ($tag:literal, $item:expr, $deliver:expr) => {{
+        let item = $item;
+        #[allow(unused_mut)]
+        let mut deliver = $deliver;
+        #[cfg(feature = "chaos")]
+        if $crate::__chaos_should_act!($tag, "duplicate") {
+            let _ = deliver(::std::clone::Clone::clone(&item));
+        }
+        deliver(item)
+    }};
+}
+
+/// Runs `op_a` then `op_b`, or `op_b` then `op_a` when the failpoint is enabled, and
+/// evaluates to `(result_a, result_b)`.
+///
+/// Both operands are expressions (call a closure inside one to pass it). Each is evaluated
+/// exactly once, with all of its side effects; only the order changes, and the results are
+/// always returned in the original positions. Surfaces bugs that depend on two operations
+/// happening in a particular order.
+///
+/// # Example
+/// ```rust
+/// let mut log = Vec::new();
+/// let (a, b) = chaos_rs::maybe_reorder!("write_order", {
+///     log.push("index");
+///     1
+/// }, {
+///     log.push("data");
+///     2
+/// });
+/// assert_eq!((a, b), (1, 2));
+/// ```
+#[macro_export]
+macro_rules! maybe_reorder {
+    ($tag:literal, $op_a:expr, $op_b:expr) => {{
+        #[cfg(feature = "chaos")]
+        let swapped = $crate::__chaos_should_act!($tag, "reorder");
+        #[cfg(not(feature = "chaos"))]
+        let swapped = false;
+        if swapped {
+            let b = $op_b;
+            let a = $op_a;
+            (a, b)
+        } else {
+            let a = $op_a;
+            let b = $op_b;
+            (a, b)
+        }
+    }};
+}
+
+/// Panics when the failpoint is enabled.
+///
+/// # Example
+/// ```rust
+/// fn critical() {
+///     chaos_rs::maybe_panic!("unexpected_panic");
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_panic {
+    ($tag:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!($tag, "panic") {
+                panic!($tag);
+            }
+        }
+    };
+}
+
+/// Panics when the failpoint is enabled, for use inside async functions.
+///
+/// Behaves like `maybe_panic!`, but sleeps in a configured chain are awaited instead of
+/// blocking the thread. The panic unwinds out of the enclosing future's `poll`, so it can
+/// be caught with `with_failpoint_async!`'s `panic` mode or observed as a panicked task.
+///
+/// # Example
+/// ```rust
+/// async fn critical() {
+///     chaos_rs::maybe_panic_async!("unexpected_panic");
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_panic_async {
+    ($tag:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!(async $tag, "panic") {
+                panic!($tag);
+            }
+        }
+    };
+}
+
+/// Sleeps for a given number of milliseconds when the failpoint is enabled.
+///
+/// A duration set with `configure_sleep` takes precedence over `millis`.
+///
+/// # Example
+/// ```rust
+/// chaos_rs::maybe_sleep!("slow_io", 500);
+/// ```
+#[macro_export]
+macro_rules! maybe_sleep {
+    ($tag:literal, $millis:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!($tag, "sleep") {
+                let duration = std::time::Duration::from_millis($millis);
+                $crate::__failpoint_internal::sleep_internal($tag, duration);
+            }
+        }
+    };
+}
+
+/// If the specified failpoint is enabled, this macro will pause the asynchronous
+/// execution for a given number of milliseconds.
+///
+/// A duration set with `configure_sleep` takes precedence over `millis`.
+///
+/// # Example
+/// ```rust
+/// async fn fetch() {
+///     chaos_rs::maybe_sleep_async!("slow_io", 500);
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_sleep_async {
+    ($tag:literal, $millis:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!(async $tag, "sleep") {
+                let duration = $crate::__failpoint_internal::sleep_duration(
+                    $tag,
+                    std::time::Duration::from_millis($millis),
+                );
+                $crate::__failpoint_internal::record_sleep($tag, duration);
+                $crate::__failpoint_internal::sleep_async_internal(duration).await;
+            }
+        }
+    };
+}
+
+/// Makes the surrounding operation `factor` times slower when the failpoint is enabled.
+///
+/// Marks the end of an operation that began with `chaos_rs::slow_begin(tag)` on the same
+/// thread. The time in between is the baseline, and the macro sleeps
+/// `baseline * (factor - 1)`, modeling a proportional slowdown rather than a fixed delay.
+/// Without a matching `slow_begin`, or with a `factor` of 1 or less, it does nothing.
+///
+/// # Example
+/// ```rust
+/// fn query() -> u32 {
+///     chaos_rs::slow_begin("slow_query");
+///     let rows = 42; // the real work
+///     chaos_rs::maybe_slow!("slow_query", 3.0);
+///     rows
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_slow {
+    ($tag:literal, $factor:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!($tag, "slow down") {
+                $crate::__failpoint_internal::slow_end($tag, $factor);
+            }
+        }
+    };
+}
+
+/// Evaluates to a clock reading, skewed by the offset set with `configure_clock_skew` when
+/// the failpoint is enabled.
+///
+/// `now` is the real reading, an `Instant` or a `SystemTime`, and is always evaluated once.
+/// When the failpoint is disabled, or no skew is configured, it is returned unchanged, so
+/// time-dependent code can route its clock reads through the macro and simulate clock
+/// jumps without touching the system clock.
+///
+/// # Example
+/// ```rust
+/// use std::time::SystemTime;
+///
+/// fn is_expired(deadline: SystemTime) -> bool {
+///     chaos_rs::maybe_now!("token_clock", SystemTime::now()) >= deadline
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_now {
+    ($tag:literal, $now:expr) => {{
+        let now = $now;
+        #[cfg(feature = "chaos")]
+        let now = match $crate::__chaos_should_act!($tag, "skew clock") {
+            true => match $crate::__failpoint_internal::clock_skew($tag) {
+                Some(skew) => $crate::SkewClock::skewed_by(now, skew),
+                None => now,
+            },
+            false => now,
+        };
+        now
+    }};
+}
+
+/// Evaluates to `transform(value)` when the failpoint is enabled, and to `value` otherwise.
+///
+/// `value` is evaluated once and moved into the transform, which takes it by value and
+/// returns a value of the same type, so it can corrupt owned data in place (flip a byte,
+/// truncate a string) without cloning. Use it on return values to simulate subtle data
+/// corruption rather than an outright failure.
+///
+/// # Example
+/// ```rust
+/// fn checksum(data: &[u8]) -> u32 {
+///     let sum = data.iter().map(|b| *b as u32).sum();
+///     chaos_rs::maybe_transform!("bad_checksum", sum, |sum: u32| sum ^ 1)
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_transform {
+    ($tag:literal, $value:expr, $transform:expr) => {{
+        let value = $value;
+        #[cfg(feature = "chaos")]
+        let value = match $crate::__chaos_should_act!($tag, "transform") {
+            true => ($transform)(value),
+            false => value,
+        };
+        value
+    }};
+}
+
+/// Sleeps until the given `std::time::Instant` when the failpoint is enabled.
+///
+/// Useful for lining several threads up on a shared deadline. If the instant has already
+/// passed this returns immediately without sleeping.
+///
+/// # Example
+/// ```rust
+/// use std::time::{Duration, Instant};
+///
+/// fn aligned(start: Instant) {
+///     chaos_rs::maybe_sleep_until!("barrier", start + Duration::from_millis(50));
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_sleep_until {
+    ($tag:literal, $deadline:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!($tag, "sleep") {
+                $crate::__failpoint_internal::sleep_until_internal($tag, $deadline);
+            }
+        }
+    };
+}
+
+/// Sleeps for a random duration between `min_ms` and `max_ms` (inclusive) when the failpoint
+/// is enabled.
+///
+/// The duration is drawn from the generator seeded by `set_seed`.
+///
+/// # Example
+/// ```rust
+/// chaos_rs::maybe_sleep_range!("jittery_io", 100, 500);
+/// ```
+#[macro_export]
+macro_rules! maybe_sleep_range {
+    ($tag:literal, $min_ms:literal, $max_ms:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!($tag, "sleep") {
+                let duration = $crate::__failpoint_internal::random_duration($min_ms, $max_ms);
+                $crate::__failpoint_internal::sleep_internal($tag, duration);
+            }
+        }
+    };
+}
+
+/// Async variant of `maybe_sleep_range!`.
+///
+/// The duration is drawn only when the sleep is going to happen, right before it is
+/// awaited, so a configured action doesn't use up a draw. With a fixed seed and Tokio's
+/// paused clock (`tokio` feature) the virtual time advanced is deterministic.
+///
+/// # Example
+/// ```rust
+/// async fn fetch() {
+///     chaos_rs::maybe_sleep_range_async!("jittery_io", 100, 500);
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_sleep_range_async {
+    ($tag:literal, $min_ms:literal, $max_ms:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!(async $tag, "sleep") {
+                let duration = $crate::__failpoint_internal::sleep_duration(
+                    $tag,
+                    $crate::__failpoint_internal::random_duration($min_ms, $max_ms),
+                );
+                $crate::__failpoint_internal::record_sleep($tag, duration);
+                $crate::__failpoint_internal::sleep_async_internal(duration).await;
+            }
+        }
+    };
+}
+
+/// Like `maybe_fail!`, but always compiled and gated by the runtime master switch
+/// (`set_runtime_chaos`) instead of the `chaos` feature.
+///
+/// For builds that can't use a cargo feature to gate chaos. While the switch is off, which
+/// is the default, an evaluation costs a single relaxed atomic load, while the feature-gated
+/// macros cost nothing at all when compiled out. The failpoint code also stays in the
+/// binary either way.
+///
+/// # Example
+/// ```rust
+/// fn fetch() -> Result<(), String> {
+///     chaos_rs::maybe_fail_runtime!("runtime_fetch");
+///     Ok(())
+/// }
+///
+/// assert!(fetch().is_ok());
+/// ```
+#[macro_export]
+macro_rules! maybe_fail_runtime {
+    ($tag:literal) => {
+        $crate::maybe_fail_runtime!($tag, $tag.into())
+    };
+    ($tag:literal, $err:expr) => {
+        if $crate::__failpoint_internal::is_runtime_chaos_enabled()
+            && $crate::__chaos_should_fail!($tag, "fail")
+        {
+            return Err($err);
+        }
+    };
+}
+
+/// Like `maybe_panic!`, but gated by the runtime master switch; see `maybe_fail_runtime!`.
+///
+/// # Example
+/// ```rust
+/// fn critical() {
+///     chaos_rs::maybe_panic_runtime!("runtime_panic");
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_panic_runtime {
+    ($tag:literal) => {
+        if $crate::__failpoint_internal::is_runtime_chaos_enabled()
+            && $crate::__chaos_should_act!($tag, "panic")
+        {
+            panic!($tag);
+        }
+    };
+}
+
+/// Like `maybe_sleep!`, but gated by the runtime master switch; see `maybe_fail_runtime!`.
+///
+/// # Example
+/// ```rust
+/// fn slow() {
+///     chaos_rs::maybe_sleep_runtime!("runtime_sleep", 100);
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_sleep_runtime {
+    ($tag:literal, $millis:literal) => {
+        if $crate::__failpoint_internal::is_runtime_chaos_enabled()
+            && $crate::__chaos_should_act!($tag, "sleep")
+        {
+            $crate::__failpoint_internal::sleep_internal(
+                $tag,
+                std::time::Duration::from_millis($millis),
+            );
+        }
+    };
+}
+
+/// Awaits `barrier.wait()` when the failpoint is enabled, making concurrent tasks rendezvous
+/// at this point.
+///
+/// Meant for a shared `tokio::sync::Barrier` (any type with an async `wait` works), usually
+/// behind an `Arc`. The barrier must be created for exactly as many tasks as will reach it
+/// while the tag is enabled: with fewer, every task waits forever. When the failpoint is
+/// disabled the barrier is not touched.
+///
+/// # Example
+/// ```rust
+/// use std::sync::Arc;
+/// use tokio::sync::Barrier;
+///
+/// async fn worker(barrier: Arc<Barrier>) {
+///     // ... first half of the work
+///     chaos_rs::maybe_await_barrier!("handoff", barrier);
+///     // ... second half, started by all workers together
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_await_barrier {
+    ($tag:literal, $barrier:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!(async $tag, "await barrier") {
+                let _ = $barrier.wait().await;
+            }
+        }
+    };
+}
+
+/// Blocks the current thread when the failpoint is enabled until `release_failpoint` is
+/// called for the tag.
+///
+/// Disabling the tag with `disable_failpoint` also releases any blocked threads, so a test
+/// that cleans up its failpoints can't leave them parked.
+///
+/// # Example
+/// ```rust
+/// fn commit() {
+///     chaos_rs::maybe_block!("before_commit");
+///     // ... runs once the test releases the failpoint
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_block {
+    ($tag:literal) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!($tag, "block") {
+                $crate::__failpoint_internal::block_internal($tag);
+            }
+        }
+    };
+}
+
+/// Waits when the failpoint is enabled until `release_failpoint` is called for the tag or
+/// `timeout` (a `Duration`) elapses.
+///
+/// If the timeout elapses first, execution simply proceeds as if it had been released, so a
+/// forgotten release can't hang a test forever. Disabling the tag also releases waiters.
+///
+/// # Example
+/// ```rust
+/// async fn commit() {
+///     chaos_rs::maybe_block_async!("before_commit", std::time::Duration::from_secs(5));
+///     // ... runs once released or after 5 seconds
+/// }
+/// ```
+#[macro_export]
+macro_rules! maybe_block_async {
+    ($tag:literal, $timeout:expr) => {
+        #[cfg(feature = "chaos")]
+        {
+            if $crate::__chaos_should_act!(async $tag, "block") {
+                $crate::__failpoint_internal::block_async_internal($tag, $timeout).await;
+            }
+        }
+    };
+}
+
+/// Runs a code block with a failpoint enabled and validates its effect.
+///
+/// Supported modes:
+/// - `panic`: Expects the code to panic when the failpoint is active.
+/// - `panic, captured => code`: Like `panic`, but binds the caught panic payload
+///   (`Box<dyn Any + Send>`) to `captured` in the enclosing scope. The binding only exists
+///   when the `chaos` feature is enabled.
+/// - `no_panic`: The inverse of `panic`: expects the code to run to completion without
+///   panicking, so the failpoint degrades gracefully instead of crashing. A panic is caught
+///   and reported as a failure naming the tag and the panic message.
+/// - `error`: Expects the code to return `Err` when the failpoint is active.
+/// - `error_matching(predicate)`: Like `error`, but also requires `predicate(&err)` to
+///   return `true`, so the test checks which error came out, not just that one did.
+/// - Sleep validation: Verifies that code sleeps somewhere in the range of `min_ms` - `tolerance` and `min_ms` + `tolerance` when failpoint is active.
+/// - `at_least(min_ms)`: Verifies that code sleeps at least `min_ms`, with no upper bound.
+/// - `total_sleep(expected_ms, tolerance_ms)`: Verifies that the sleeps injected for the tag
+///   during the block add up to `expected_ms` ± `tolerance_ms`, however many there were.
+///   Uses the injected durations from [`sleep_stats`](crate::sleep_stats), not wall time.
+/// - `yielded(min_ms, max_cpu_ms)`: Verifies that at least `min_ms` of wall time passes while
+///   the thread uses at most `max_cpu_ms` of CPU time, so a busy-loop can't pass for a sleep.
+///   The CPU check needs the `cpu-time` feature on a Unix platform (per-thread on Linux,
+///   per-process elsewhere, see [`thread_cpu_time`](crate::thread_cpu_time)); otherwise only
+///   the wall-time bound is checked.
+///
+/// When an assertion fails, the panic message is followed by the tag's context, to show what
+/// the failpoint actually did:
+///
+/// ```text
+/// failpoint '<tag>' context:
+///   hits: <hits since the last reset>
+///   action: <configured chain, e.g. [Error], or "macro default">
+///   recent injections:
+///     <action> at <file>:<line>:<column>
+/// ```
+///
+/// The recent injections are the tag's last five entries in the
+/// [`injection_log`](crate::injection_log), oldest first.
+///
+/// The millisecond bounds of the timing modes are multiplied by the factor set with
+/// [`set_time_scale`](crate::set_time_scale), just like the sleeps they measure; the CPU
+/// limit of `yielded` is not.
+///
+/// # Examples
+///
+/// Expects a panic:
+/// ```rust
+/// chaos_rs::with_failpoint!("panic_test", panic, {
+///     chaos_rs::maybe_panic!("panic_test");
+/// });
+/// ```
+///
+/// Expects graceful degradation instead of a panic:
+/// ```rust
+/// fn lookup() -> Option<u32> {
+///     chaos_rs::maybe_degrade!("cache_miss", || {});
+///     Some(1)
+/// }
+///
+/// chaos_rs::with_failpoint!("cache_miss", no_panic, lookup());
+/// ```
+///
+/// Expects a panic and inspects its payload:
+/// ```rust
+/// chaos_rs::with_failpoint!("payload_test", panic, payload => {
+///     chaos_rs::maybe_panic!("payload_test");
+/// });
+/// # #[cfg(feature = "chaos")]
+/// assert_eq!(payload.downcast_ref::<&str>(), Some(&"payload_test"));
+/// ```
+///
+/// Expects an error:
+/// ```rust
+/// chaos_rs::with_failpoint!("error_test", error, {
+///     fn test() -> Result<(), ()> {
+///         chaos_rs::maybe_fail!("error_test", ());
+///         Ok(())
+///     }
+///     test()
+/// });
+/// ```
+///
+/// Expects a specific error:
+/// ```rust
+/// #[derive(Debug)]
+/// enum FetchError {
+///     Timeout,
+///     NotFound,
+/// }
+///
+/// fn fetch() -> Result<(), FetchError> {
+///     chaos_rs::maybe_fail!("timeout_test", FetchError::Timeout);
+///     Err(FetchError::NotFound)
+/// }
+///
+/// chaos_rs::with_failpoint!(
+///     "timeout_test",
+///     error_matching(|e| matches!(e, FetchError::Timeout)),
+///     fetch()
+/// );
+/// ```
+///
+/// Expects an error within at most 20 runs, for failpoints that only fire some of the time.
+/// The code is run again until it returns `Err`, and the macro panics if all attempts
+/// return `Ok`. The failpoint is disabled once the loop ends; settings such as its
+/// probability are left for the caller to clear:
+/// ```rust
+/// fn flaky() -> Result<(), ()> {
+///     chaos_rs::maybe_fail!("flaky_test", ());
+///     Ok(())
+/// }
+///
+/// # #[cfg(feature = "chaos")]
+/// chaos_rs::__failpoint_internal::configure_probability("flaky_test", 0.5);
+/// chaos_rs::with_failpoint!("flaky_test", error_within(20), flaky());
+/// # #[cfg(feature = "chaos")]
+/// chaos_rs::__failpoint_internal::clear_probability("flaky_test");
+/// ```
+///
+/// Expects the operation to sleep for 200 ± 50ms (150 - 250 range):
+/// ```rust
+/// chaos_rs::with_failpoint!("sleep_test", 200, 50, {
+///     chaos_rs::maybe_sleep!("sleep_test", 200);
+/// });
+/// ```
+///
+/// Expects the operation to sleep for at least 100ms:
+/// ```rust
+/// chaos_rs::with_failpoint!("min_sleep_test", at_least(100), {
+///     chaos_rs::maybe_sleep!("min_sleep_test", 150);
+/// });
+/// ```
+///
+/// Expects several sleeps to add up to 300 ± 20ms:
+/// ```rust
+/// chaos_rs::with_failpoint!("total_sleep_test", total_sleep(300, 20), {
+///     for _ in 0..3 {
+///         chaos_rs::maybe_sleep!("total_sleep_test", 100);
+///     }
+/// });
+/// ```
+///
+/// Expects the operation to really sleep for 100ms rather than spin:
+/// ```rust
+/// chaos_rs::with_failpoint!("yield_test", yielded(100, 20), {
+///     chaos_rs::maybe_sleep!("yield_test", 120);
+/// });
+/// ```
+#[macro_export]
+macro_rules! with_failpoint {
+    ($tag:literal, panic, $captured:ident => $code:expr) => {
+        #[cfg(feature = "chaos")]
+        let $captured: Box<dyn std::any::Any + Send> = {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let result = std::panic::catch_unwind(|| $code);
+            $crate::__failpoint_internal::disable_failpoint($tag);
+            match result {
+                Ok(_) => panic!(
+                    "Expected panic from failpoint '{}', but none occurred{}",
+                    $tag,
+                    $crate::__failpoint_internal::failure_context($tag)
+                ),
+                Err(payload) => payload,
+            }
+        };
+    };
+
+    ($tag:literal, panic, $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let result = std::panic::catch_unwind(|| $code);
+            $crate::__failpoint_internal::disable_failpoint($tag);
+            match result {
+                Ok(_) => panic!(
+                    "Expected panic from failpoint '{}', but none occurred{}",
+                    $tag,
+                    $crate::__failpoint_internal::failure_context($tag)
+                ),
+                Err(_) => {}
+            }
+        }
+    }};
+
+    ($tag:literal, no_panic, $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let result = std::panic::catch_unwind(|| $code);
+            $crate::__failpoint_internal::disable_failpoint($tag);
+            if let Err(payload) = result {
+                panic!(
+                    "Expected no panic with failpoint '{}' enabled, but the code panicked: {}{}",
+                    $tag,
+                    $crate::__failpoint_internal::panic_message(&*payload),
+                    $crate::__failpoint_internal::failure_context($tag)
+                );
+            }
+        }
+    }};
+
+    ($tag:literal, error, $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let result = $code;
+            $crate::__failpoint_internal::disable_failpoint($tag);
+
+            match result {
+                Err(_) => {}
+                Ok(_) => panic!(
+                    "Expected error from failpoint '{}', but function returned Ok{}",
+                    $tag,
+                    $crate::__failpoint_internal::failure_context($tag)
+                ),
+            }
+        }
+    }};
+
+    ($tag:literal, error_matching($predicate:expr), $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let result = $code;
+            $crate::__failpoint_internal::disable_failpoint($tag);
+
+            match result {
+                Err(err) => assert!(
+                    $crate::__failpoint_internal::error_matches(&err, $predicate),
+                    "Error from failpoint '{}' did not match the expected predicate{}",
+                    $tag,
+                    $crate::__failpoint_internal::failure_context($tag)
+                ),
+                Ok(_) => panic!(
+                    "Expected error from failpoint '{}', but function returned Ok{}",
+                    $tag,
+                    $crate::__failpoint_internal::failure_context($tag)
+                ),
+            }
+        }
+    }};
+
+    ($tag:literal, error_within($attempts:expr), $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            let attempts: usize = $attempts;
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let failed = (0..attempts).any(|_| $code.is_err());
+            $crate::__failpoint_internal::disable_failpoint($tag);
+
+            assert!(
+                failed,
+                "Expected error from failpoint '{}' within {} attempts, but every attempt returned Ok{}",
+                $tag,
+                attempts,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+        }
+    }};
+
+    ($tag:literal, at_least($min_ms:literal), $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let start = std::time::Instant::now();
+            $code;
+            let elapsed = start.elapsed();
+            $crate::__failpoint_internal::disable_failpoint($tag);
+
+            let min = $crate::__failpoint_internal::scaled_millis($min_ms);
+
+            assert!(
+                elapsed >= min,
+                "Expected sleep of at least {:?} from failpoint '{}', got {:?}{}",
+                min,
+                $tag,
+                elapsed,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+        }
+    }};
+
+    ($tag:literal, total_sleep($expected_ms:literal, $tolerance_ms:literal), $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let before = $crate::sleep_stats($tag).total;
+            $code;
+            let total = $crate::sleep_stats($tag).total.saturating_sub(before);
+            $crate::__failpoint_internal::disable_failpoint($tag);
+
+            let expected = $crate::__failpoint_internal::scaled_millis($expected_ms);
+            let tolerance = $crate::__failpoint_internal::scaled_millis($tolerance_ms);
+            assert!(
+                total.abs_diff(expected) <= tolerance,
+                "Expected injected sleeps from failpoint '{}' to total {:?} ± {:?}, got {:?}{}",
+                $tag,
+                expected,
+                tolerance,
+                total,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+        }
+    }};
+
+    ($tag:literal, yielded($min_ms:literal, $max_cpu_ms:literal), $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let cpu_start = $crate::thread_cpu_time();
+            let start = std::time::Instant::now();
+            $code;
+            let elapsed = start.elapsed();
+            let cpu_end = $crate::thread_cpu_time();
+            $crate::__failpoint_internal::disable_failpoint($tag);
+
+            let min = $crate::__failpoint_internal::scaled_millis($min_ms);
+            assert!(
+                elapsed >= min,
+                "Expected sleep of at least {:?} from failpoint '{}', got {:?}{}",
+                min,
+                $tag,
+                elapsed,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+
+            if let (Some(cpu_start), Some(cpu_end)) = (cpu_start, cpu_end) {
+                let cpu = cpu_end.saturating_sub(cpu_start);
+                let max_cpu = std::time::Duration::from_millis($max_cpu_ms);
+                assert!(
+                    cpu <= max_cpu,
+                    "Expected failpoint '{}' to yield, but it used {:?} of CPU time over {:?} (limit {:?}){}",
+                    $tag,
+                    cpu,
+                    elapsed,
+                    max_cpu,
+                    $crate::__failpoint_internal::failure_context($tag)
+                );
+            }
+        }
+    }};
+
+    ($tag:literal, $min_ms:literal, $tolerance_ms:literal, $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let start = std::time::Instant::now();
+            $code;
+            let elapsed = start.elapsed();
+            $crate::__failpoint_internal::disable_failpoint($tag);
+
+            let max = $crate::__failpoint_internal::scaled_millis($min_ms + $tolerance_ms);
+            let min = $crate::__failpoint_internal::scaled_millis($min_ms - $tolerance_ms);
+
+            assert!(
+                elapsed <= max && elapsed >= min,
+                "Expected sleep between {:?} and {:?} from failpoint '{}', got {:?}{}",
+                min,
+                max,
+                $tag,
+                elapsed,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+        }
+    }};
+}
+/// Runs a code block with a failpoint enabled and asserts its elapsed time matches a
+/// [`SleepExpectation`](crate::SleepExpectation).
+///
+/// # Example
+/// ```rust
+/// use chaos_rs::SleepExpectation;
+///
+/// chaos_rs::with_failpoint_timed!("timed_test", SleepExpectation::between(150, 260), {
+///     chaos_rs::maybe_sleep!("timed_test", 200);
+/// });
+/// ```
+#[macro_export]
+macro_rules! with_failpoint_timed {
+    ($tag:literal, $expectation:expr, $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            let expectation: $crate::SleepExpectation = $expectation;
+            let expectation = expectation.scaled();
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let start = std::time::Instant::now();
+            $code;
+            let elapsed = start.elapsed();
+            $crate::__failpoint_internal::disable_failpoint($tag);
+
+            assert!(
+                expectation.contains(elapsed),
+                "Expected sleep between {:?} and {:?} from failpoint '{}', got {:?}{}",
+                expectation.min(),
+                expectation.max(),
+                $tag,
+                elapsed,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+        }
+    }};
+}
+
+/// Asserts that a failpoint has fired exactly `expected` times since its counter was last
+/// reset.
+///
+/// # Example
+/// ```rust
+/// fn call() -> Result<(), String> {
+///     chaos_rs::maybe_fail!("hits_doc");
+///     Ok(())
+/// }
+///
+/// chaos_rs::__failpoint_internal::enable_failpoint("hits_doc");
+/// let _ = call();
+/// let _ = call();
+/// chaos_rs::__failpoint_internal::disable_failpoint("hits_doc");
+/// chaos_rs::assert_failpoint_hits!("hits_doc", 2);
+/// ```
+#[macro_export]
+macro_rules! assert_failpoint_hits {
+    ($tag:literal, $expected:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            let expected: u64 = $expected;
+            let hits = $crate::__failpoint_internal::failpoint_hits($tag);
+            assert!(
+                hits == expected,
+                "Expected failpoint '{}' to fire {} times, but it fired {} times{}",
+                $tag,
+                expected,
+                hits,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+        }
+    }};
+}
+
+/// Asserts that a failpoint has fired at least `expected` times since its counter was last
+/// reset.
+///
+/// # Example
+/// ```rust
+/// chaos_rs::assert_failpoint_hits_at_least!("never_enabled", 0);
+/// ```
+#[macro_export]
+macro_rules! assert_failpoint_hits_at_least {
+    ($tag:literal, $expected:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            let expected: u64 = $expected;
+            let hits = $crate::__failpoint_internal::failpoint_hits($tag);
+            assert!(
+                hits >= expected,
+                "Expected failpoint '{}' to fire at least {} times, but it fired {} times{}",
+                $tag,
+                expected,
+                hits,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+        }
+    }};
+}
+
+/// Enables a failpoint and retries `operation` until it returns `Ok`, asserting that it
+/// does so within `max_attempts` calls.
+///
+/// Meant for failpoints that heal on their own, such as `maybe_fail_times!`: with a tag
+/// that fails `n` times, the assertion holds exactly when `max_attempts` is greater than
+/// `n`. A failpoint that never heals exhausts the attempts and fails the assertion. The
+/// tag is disabled afterwards, even if `operation` panics.
+///
+/// # Example
+/// ```rust
+/// fn connect() -> Result<(), String> {
+///     chaos_rs::maybe_fail_times!("eventual_doc", 2, "not ready".to_string());
+///     Ok(())
+/// }
+///
+/// chaos_rs::assert_eventually_succeeds!("eventual_doc", 3, || connect());
+/// ```
+#[macro_export]
+macro_rules! assert_eventually_succeeds {
+    ($tag:literal, $max_attempts:expr, $operation:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            let max_attempts: u64 = $max_attempts;
+            let mut operation = $operation;
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let guard = $crate::__failpoint_internal::DisableOnDrop($tag);
+            let succeeded = (0..max_attempts).any(|_| operation().is_ok());
+            drop(guard);
+            assert!(
+                succeeded,
+                "Expected operation to succeed within {} attempts with failpoint '{}' enabled{}",
+                max_attempts,
+                $tag,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+        }
+    }};
+}
+
+/// Async variant of `with_failpoint!`; awaits `code` with the failpoint enabled and
+/// validates its effect.
+///
+/// Supported modes:
+/// - `panic`: Expects the future to panic when the failpoint is active.
+/// - `error`: Expects the future to resolve to `Err`. No unwinding machinery is involved,
+///   and the tag is disabled even if the enclosing future is dropped mid-await.
+/// - Sleep validation: Verifies that the future takes `min_ms` ± `tolerance_ms`.
+/// - `error_and_timing(min_ms, max_ms)`: Expects the future to resolve to `Err` after
+///   taking between `min_ms` and `max_ms`, for failpoints that both delay and fail. Like
+///   `error`, the tag is disabled even if the enclosing future is dropped mid-await.
+///
+/// # Examples
+/// ```rust
+/// async fn risky() {
+///     chaos_rs::maybe_panic_async!("async_panic");
+/// }
+///
+/// async fn test() {
+///     chaos_rs::with_failpoint_async!("async_panic", panic, risky());
+/// }
+/// ```
+///
+/// Expects an error:
+/// ```rust
+/// async fn fetch() -> Result<(), String> {
+///     chaos_rs::maybe_fail!("async_error");
+///     Ok(())
+/// }
+///
+/// async fn test() {
+///     chaos_rs::with_failpoint_async!("async_error", error, fetch());
+/// }
+/// ```
+///
+/// Expects an error after a delay of 80 - 150ms:
+/// ```rust
+/// async fn slow_fetch() -> Result<(), String> {
+///     chaos_rs::maybe_sleep_async!("slow_error", 100);
+///     chaos_rs::maybe_fail!("slow_error");
+///     Ok(())
+/// }
+///
+/// async fn test() {
+///     chaos_rs::with_failpoint_async!("slow_error", error_and_timing(80, 150), slow_fetch());
+/// }
+/// ```
+#[macro_export]
+macro_rules! with_failpoint_async {
+    ($tag:literal, error, $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let guard = $crate::__failpoint_internal::DisableOnDrop($tag);
+            let result = $code.await;
+            drop(guard);
+
+            match result {
+                Err(_) => {}
+                Ok(_) => panic!(
+                    "Expected error from failpoint '{}', but future returned Ok{}",
+                    $tag,
+                    $crate::__failpoint_internal::failure_context($tag)
+                ),
+            }
+        }
+    }};
+
+    ($tag:literal, error_and_timing($min_ms:literal, $max_ms:literal), $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let guard = $crate::__failpoint_internal::DisableOnDrop($tag);
+            let start = std::time::Instant::now();
+            let result = $code.await;
+            let elapsed = start.elapsed();
+            drop(guard);
+
+            if result.is_ok() {
+                panic!(
+                    "Expected error from failpoint '{}', but future returned Ok{}",
+                    $tag,
+                    $crate::__failpoint_internal::failure_context($tag)
+                );
+            }
+
+            let window = $crate::SleepExpectation::between($min_ms, $max_ms).scaled();
+            assert!(
+                window.contains(elapsed),
+                "Expected error after between {:?} and {:?} from failpoint '{}', got {:?}{}",
+                window.min(),
+                window.max(),
+                $tag,
+                elapsed,
+                $crate::__failpoint_internal::failure_context($tag)
+            );
+        }
+    }};
+
+    ($tag:literal, panic, $code:expr) => {{
+        #[cfg(feature = "chaos")]
+        {
+            $crate::__failpoint_internal::enable_failpoint($tag);
+            let result = $crate::__failpoint_internal::catch_unwind_async($code).await;
+            $crate::__failpoint_internal::disable_failpoint($tag);
+            match result {
+                Ok(_) => panic!(
+                    "Expected panic from failpoint '{}', but none occurred{}",
+                    $tag,
+                    $crate::__failpoint_internal::failure_context($tag)
+                ),
+                Err(_) => {}
+            }
+        }
+    }};
+
     ($tag:literal, $min_ms:literal, $tolerance_ms:literal, $code:expr) => {{
         #[cfg(feature = "chaos")]
         {
@@ -195,16 +2120,17 @@ macro_rules! with_failpoint_async {
             let elapsed = start.elapsed();
             $crate::__failpoint_internal::disable_failpoint($tag);
 
-            let max = std::time::Duration::from_millis($min_ms + $tolerance_ms);
-            let min = std::time::Duration::from_millis($min_ms - $tolerance_ms);
+            let max = $crate::__failpoint_internal::scaled_millis($min_ms + $tolerance_ms);
+            let min = $crate::__failpoint_internal::scaled_millis($min_ms - $tolerance_ms);
 
             assert!(
                 elapsed <= max && elapsed >= min,
-                "Expected sleep between {:?} and {:?} from failpoint '{}', got {:?}",
+                "Expected sleep between {:?} and {:?} from failpoint '{}', got {:?}{}",
                 min,
                 max,
                 $tag,
-                elapsed
+                elapsed,
+                $crate::__failpoint_internal::failure_context($tag)
             );
         }
     }};
@@ -0,0 +1,49 @@
+use crate::__failpoint_internal::{FAILPOINTS, total_hit_counts};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Encodes failpoint activity in the Prometheus text exposition format.
+///
+/// Two metric families are produced, each labelled with `tag`:
+/// - `chaos_failpoint_enabled` (gauge): `1` for every currently enabled failpoint.
+/// - `chaos_failpoint_hits` (counter): how many times each tag has fired since the process
+///   started, for every tag that has fired at least once. Resetting hit counters doesn't
+///   lower it, so scrapers never see the counter go backwards.
+///
+/// Samples are sorted by tag, so the output is stable and can be served directly from a
+/// scrape endpoint.
+///
+/// # Example
+/// ```rust
+/// let text = chaos_rs::encode_metrics();
+/// assert!(text.contains("# TYPE chaos_failpoint_hits counter"));
+/// ```
+pub fn encode_metrics() -> String {
+    let enabled: BTreeSet<&'static str> = FAILPOINTS.iter().map(|tag| *tag).collect();
+    let mut out = String::new();
+
+    out.push_str("# HELP chaos_failpoint_enabled Whether the failpoint is enabled.\n");
+    out.push_str("# TYPE chaos_failpoint_enabled gauge\n");
+    for tag in enabled {
+        let _ = writeln!(out, "chaos_failpoint_enabled{{tag=\"{}\"}} 1", escape(tag));
+    }
+
+    out.push_str("# HELP chaos_failpoint_hits How many times the failpoint has fired.\n");
+    out.push_str("# TYPE chaos_failpoint_hits counter\n");
+    for (tag, hits) in total_hit_counts() {
+        let _ = writeln!(
+            out,
+            "chaos_failpoint_hits{{tag=\"{}\"}} {}",
+            escape(tag),
+            hits
+        );
+    }
+    out
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
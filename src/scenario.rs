@@ -0,0 +1,240 @@
+use crate::__failpoint_internal::{
+    DisableOnDrop, FAILPOINTS, disable_failpoint, enable_failpoint, failpoint_hits,
+};
+use crate::SleepExpectation;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+use std::time::{Duration, Instant};
+
+/// The outcome of [`run_chaos_scenario`], suitable for aggregating into a report.
+///
+/// Serializable with the `serde` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ScenarioResult {
+    /// The name passed to `run_chaos_scenario`.
+    pub name: String,
+    /// Whether the body returned `Err` or panicked, which is what an injected failure
+    /// is expected to cause.
+    pub failed: bool,
+    /// Whether the body panicked.
+    pub panicked: bool,
+    /// The `Debug` form of the returned error, or the panic message.
+    pub error: Option<String>,
+    /// Wall-clock time spent in the body.
+    pub duration: Duration,
+    /// How many times each tag enabled by `setup` fired during the body.
+    pub hits: BTreeMap<String, u64>,
+}
+
+/// Runs `setup` to enable failpoints, then runs `body` and records what happened.
+///
+/// Tags enabled by `setup` are disabled again once `body` finishes, even if it panics.
+///
+/// # Example
+/// ```rust
+/// fn fetch() -> Result<(), String> {
+///     chaos_rs::maybe_fail!("scenario_fetch");
+///     Ok(())
+/// }
+///
+/// let result = chaos_rs::run_chaos_scenario(
+///     "fetch fails",
+///     || chaos_rs::__failpoint_internal::enable_failpoint("scenario_fetch"),
+///     fetch,
+/// );
+/// # #[cfg(feature = "chaos")]
+/// assert!(result.failed);
+/// ```
+pub fn run_chaos_scenario<T, E: std::fmt::Debug>(
+    name: &str,
+    setup: impl FnOnce(),
+    body: impl FnOnce() -> Result<T, E>,
+) -> ScenarioResult {
+    let before: BTreeSet<&'static str> = FAILPOINTS.iter().map(|tag| *tag).collect();
+    setup();
+    let tags: Vec<&'static str> = FAILPOINTS
+        .iter()
+        .map(|tag| *tag)
+        .filter(|tag| !before.contains(tag))
+        .collect();
+    let hits_before: Vec<u64> = tags.iter().map(|tag| failpoint_hits(tag)).collect();
+
+    let start = Instant::now();
+    let outcome = std::panic::catch_unwind(AssertUnwindSafe(body));
+    let duration = start.elapsed();
+
+    let hits = tags
+        .iter()
+        .zip(hits_before)
+        .map(|(tag, before)| (tag.to_string(), failpoint_hits(tag).saturating_sub(before)))
+        .collect();
+    for tag in &tags {
+        disable_failpoint(tag);
+    }
+
+    let (failed, panicked, error) = match outcome {
+        Ok(Ok(_)) => (false, false, None),
+        Ok(Err(err)) => (true, false, Some(format!("{:?}", err))),
+        Err(payload) => (
+            true,
+            true,
+            Some(crate::__failpoint_internal::panic_message(payload.as_ref())),
+        ),
+    };
+
+    ScenarioResult {
+        name: name.to_string(),
+        failed,
+        panicked,
+        error,
+        duration,
+        hits,
+    }
+}
+
+/// A failpoint assertion built fluently, as an alternative to the `with_failpoint!` arms.
+///
+/// [`run`](Self::run) enables the tag, runs the closure, disables the tag again and checks
+/// the expectation, returning the scenario back so several runs can be chained with `?`.
+///
+/// # Example
+/// ```rust
+/// use chaos_rs::Scenario;
+///
+/// fn fetch() -> Result<(), String> {
+///     chaos_rs::maybe_fail!("fluent_fetch");
+///     Ok(())
+/// }
+///
+/// # #[cfg(feature = "chaos")]
+/// Scenario::new("fluent_fetch")
+///     .expect_error()
+///     .run(fetch)?
+///     .run(fetch)?;
+/// # Ok::<(), chaos_rs::ExpectationError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scenario {
+    tag: &'static str,
+    expectation: Expectation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expectation {
+    Anything,
+    Error,
+    Panic,
+    Sleep(SleepExpectation),
+}
+
+impl Scenario {
+    /// Starts a scenario for `tag` that accepts any outcome until an expectation is set.
+    pub fn new(tag: &'static str) -> Self {
+        Self {
+            tag,
+            expectation: Expectation::Anything,
+        }
+    }
+
+    /// Expects the closure to return `Err`.
+    pub fn expect_error(self) -> Self {
+        Self {
+            expectation: Expectation::Error,
+            ..self
+        }
+    }
+
+    /// Expects the closure to panic.
+    pub fn expect_panic(self) -> Self {
+        Self {
+            expectation: Expectation::Panic,
+            ..self
+        }
+    }
+
+    /// Expects the closure to take between `min_ms` and `max_ms` milliseconds, inclusive.
+    pub fn expect_sleep(self, min_ms: u64, max_ms: u64) -> Self {
+        Self {
+            expectation: Expectation::Sleep(SleepExpectation::between(min_ms, max_ms)),
+            ..self
+        }
+    }
+
+    /// Runs `f` with the failpoint enabled and checks the expectation.
+    ///
+    /// A panic is caught only when one is expected; otherwise it propagates after the
+    /// failpoint has been disabled.
+    pub fn run<R: ScenarioOutcome>(
+        &self,
+        f: impl FnOnce() -> R,
+    ) -> Result<&Self, ExpectationError> {
+        enable_failpoint(self.tag);
+        let _guard = DisableOnDrop(self.tag);
+
+        let start = Instant::now();
+        let failed = match self.expectation {
+            Expectation::Panic => match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+                Ok(_) => {
+                    return Err(self.error(format_args!("expected a panic, but none occurred")));
+                }
+                Err(_) => return Ok(self),
+            },
+            _ => f().is_failure(),
+        };
+        let elapsed = start.elapsed();
+
+        match self.expectation {
+            Expectation::Error if !failed => {
+                Err(self.error(format_args!("expected an error, but the closure succeeded")))
+            }
+            Expectation::Sleep(window) if !window.contains(elapsed) => {
+                Err(self.error(format_args!(
+                    "expected a sleep between {:?} and {:?}, got {:?}",
+                    window.min(),
+                    window.max(),
+                    elapsed
+                )))
+            }
+            _ => Ok(self),
+        }
+    }
+
+    fn error(&self, detail: fmt::Arguments<'_>) -> ExpectationError {
+        ExpectationError {
+            message: format!("failpoint '{}': {}", self.tag, detail),
+        }
+    }
+}
+
+/// The value returned by a [`Scenario`] closure, telling whether it reported a failure.
+pub trait ScenarioOutcome {
+    fn is_failure(&self) -> bool;
+}
+
+impl ScenarioOutcome for () {
+    fn is_failure(&self) -> bool {
+        false
+    }
+}
+
+impl<T, E> ScenarioOutcome for Result<T, E> {
+    fn is_failure(&self) -> bool {
+        self.is_err()
+    }
+}
+
+/// Error returned when a [`Scenario`] expectation is not met.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectationError {
+    message: String,
+}
+
+impl fmt::Display for ExpectationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ExpectationError {}
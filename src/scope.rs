@@ -0,0 +1,187 @@
+use crate::__failpoint_internal::{
+    DisableOnDrop, TagAction, configure_chain, disable_failpoint, enable_failpoint, failpoint_hits,
+    restore_tag_action, tag_action,
+};
+use crate::Action;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+struct ActionGuard {
+    tag: &'static str,
+    previous: Option<TagAction>,
+}
+
+impl Drop for ActionGuard {
+    fn drop(&mut self) {
+        restore_tag_action(self.tag, self.previous.take());
+    }
+}
+
+/// Enables a failpoint for the duration of a closure and returns the closure's value.
+///
+/// The failpoint is disabled when the closure returns, including when it panics.
+/// Unlike `with_failpoint!`, no assertion is made about the closure's behavior.
+///
+/// # Example
+/// ```rust
+/// let value = chaos_rs::scoped("slow_io", || 42);
+/// assert_eq!(value, 42);
+/// ```
+#[track_caller]
+pub fn scoped<T>(tag: &'static str, f: impl FnOnce() -> T) -> T {
+    enable_failpoint(tag);
+    let _guard = DisableOnDrop(tag);
+    f()
+}
+
+/// Runs `f` once per tag with only that tag enabled, for exhaustive single-fault testing.
+///
+/// Tags are visited in order. Each iteration enables its tag, calls `f` with it, and
+/// disables it again before the next one starts, using the same cleanup as [`scoped`], so
+/// no fault leaks into the following iteration even if `f` panics. Tags outside the list
+/// are left untouched.
+///
+/// # Example
+/// ```rust
+/// fn sync() -> Result<(), String> {
+///     chaos_rs::maybe_fail!("sync_read");
+///     chaos_rs::maybe_fail!("sync_write");
+///     Ok(())
+/// }
+///
+/// chaos_rs::for_each_single_failpoint(&["sync_read", "sync_write"], |_tag| {
+///     # #[cfg(feature = "chaos")]
+///     assert!(sync().is_err());
+/// });
+/// ```
+pub fn for_each_single_failpoint(tags: &[&'static str], mut f: impl FnMut(&'static str)) {
+    for &tag in tags {
+        scoped(tag, || f(tag));
+    }
+}
+
+/// Sets the action of `tag` for the duration of a closure and returns the closure's value.
+///
+/// Whatever was configured before (any kind of action, or nothing) is restored when the
+/// closure returns or panics, so scopes can be nested. The tag's enabled state is left
+/// untouched.
+///
+/// # Example
+/// ```rust
+/// use chaos_rs::Action;
+///
+/// chaos_rs::with_action("flaky_db", Action::Error, || {
+///     // ... code that evaluates "flaky_db"
+/// });
+/// ```
+pub fn with_action<T>(tag: &'static str, action: Action, f: impl FnOnce() -> T) -> T {
+    let _guard = ActionGuard {
+        tag,
+        previous: tag_action(tag),
+    };
+    configure_chain(tag, &[action]);
+    f()
+}
+
+/// Guard returned by [`expect_hits`]; checks the hit count when dropped.
+#[must_use = "the hit count is checked when the guard is dropped"]
+pub struct HitsGuard {
+    tag: &'static str,
+    expected: u64,
+    start: u64,
+}
+
+impl Drop for HitsGuard {
+    fn drop(&mut self) {
+        let hits = failpoint_hits(self.tag).saturating_sub(self.start);
+        if hits != self.expected && !std::thread::panicking() {
+            panic!(
+                "Expected failpoint '{}' to fire {} times, but it fired {} times",
+                self.tag, self.expected, hits
+            );
+        }
+    }
+}
+
+/// Returns a guard that asserts `tag` fires exactly `n` times before the guard is dropped.
+///
+/// Only hits during the guard's lifetime count. Since the check runs on drop, it also
+/// covers bodies that leave early through `?`; bind the guard to a named variable such as
+/// `_hits` (not `_`, which drops it immediately). The check is skipped if the thread is
+/// already panicking. The tag's enabled state is left untouched.
+///
+/// # Example
+/// ```rust
+/// fn fetch() -> Result<(), String> {
+///     chaos_rs::maybe_fail!("fetch_hits");
+///     Ok(())
+/// }
+///
+/// fn run() -> Result<(), String> {
+///     let _hits = chaos_rs::expect_hits("fetch_hits", 0);
+///     fetch()?;
+///     fetch()
+/// }
+/// # run().unwrap();
+/// ```
+pub fn expect_hits(tag: &'static str, n: u64) -> HitsGuard {
+    HitsGuard {
+        tag,
+        expected: n,
+        start: failpoint_hits(tag),
+    }
+}
+
+/// Guard returned by [`enable_failpoint_scoped_async`]; disables its tag when awaited or
+/// dropped.
+#[must_use = "the failpoint is disabled when the guard is awaited or dropped"]
+pub struct AsyncFailpointGuard {
+    tag: Option<&'static str>,
+}
+
+impl AsyncFailpointGuard {
+    fn release(&mut self) {
+        if let Some(tag) = self.tag.take() {
+            disable_failpoint(tag);
+        }
+    }
+}
+
+impl Future for AsyncFailpointGuard {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        self.release();
+        Poll::Ready(())
+    }
+}
+
+impl Drop for AsyncFailpointGuard {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Enables `tag` and returns a guard that disables it again, for use across `.await`s.
+///
+/// The async counterpart of [`scoped`]: the guard owns nothing but the tag and is `Send`,
+/// so it can be held by a task across await points. Awaiting it disables the tag at a
+/// well-defined point in the task. If the task is cancelled instead, for example because
+/// its future is dropped by `select!` or a `JoinHandle::abort`, the guard is dropped with
+/// it and disables the tag then, so a cancelled test doesn't leak an enabled failpoint.
+///
+/// # Example
+/// ```rust
+/// # futures::executor::block_on(async {
+/// let guard = chaos_rs::enable_failpoint_scoped_async("flaky_rpc");
+/// // ... awaits that evaluate "flaky_rpc"
+/// guard.await;
+/// assert!(!chaos_rs::__failpoint_internal::is_failpoint_enabled("flaky_rpc"));
+/// # });
+/// ```
+#[track_caller]
+pub fn enable_failpoint_scoped_async(tag: &'static str) -> AsyncFailpointGuard {
+    enable_failpoint(tag);
+    AsyncFailpointGuard { tag: Some(tag) }
+}
@@ -0,0 +1,137 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+const IDLE: u8 = 0;
+const RECORDING: u8 = 1;
+const REPLAYING: u8 = 2;
+
+static MODE: AtomicU8 = AtomicU8::new(IDLE);
+
+struct SessionState {
+    started: Instant,
+    evaluations: HashMap<&'static str, u64>,
+    fires: Vec<FireRecord>,
+    replay: HashMap<String, BTreeSet<u64>>,
+}
+
+static STATE: LazyLock<Mutex<SessionState>> = LazyLock::new(|| {
+    Mutex::new(SessionState {
+        started: Instant::now(),
+        evaluations: HashMap::new(),
+        fires: Vec::new(),
+        replay: HashMap::new(),
+    })
+});
+
+fn state() -> MutexGuard<'static, SessionState> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// One failpoint fire captured while recording.
+///
+/// Serializable with the `serde` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FireRecord {
+    /// The tag that fired.
+    pub tag: String,
+    /// What the evaluating macro does when it fires, e.g. `"fail"` or `"sleep"`.
+    pub action: String,
+    /// Which evaluation of the tag fired, counting from 1 since recording started.
+    pub evaluation: u64,
+    /// Time since recording started.
+    pub at: Duration,
+}
+
+/// The fires captured between [`start_recording`] and [`stop_recording`], in order.
+///
+/// Serializable with the `serde` feature, so a session from a flaky run can be saved and
+/// replayed later.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChaosSession {
+    pub fires: Vec<FireRecord>,
+}
+
+/// Starts recording every failpoint fire, discarding any previous recording or replay.
+pub fn start_recording() {
+    let mut state = state();
+    state.started = Instant::now();
+    state.evaluations.clear();
+    state.fires.clear();
+    MODE.store(RECORDING, Ordering::Relaxed);
+}
+
+/// Stops recording and returns the captured session.
+pub fn stop_recording() -> ChaosSession {
+    let mut state = state();
+    MODE.store(IDLE, Ordering::Relaxed);
+    ChaosSession {
+        fires: std::mem::take(&mut state.fires),
+    }
+}
+
+/// Replays `session`, making failpoints fire exactly where they fired while it was recorded.
+///
+/// Evaluations are matched per tag by their position: the `n`th evaluation of a tag fires
+/// if and only if the `n`th evaluation fired in the recording, regardless of whether the
+/// tag is enabled, its probability or its cooldown. Give each call site its own tag;
+/// matching only holds as long as the re-run evaluates every tag the same number of times in
+/// the same order, which threads racing on one tag or a changed code path can break.
+/// Replay lasts until [`stop_replay`].
+pub fn replay(session: &ChaosSession) {
+    let mut state = state();
+    state.evaluations.clear();
+    state.replay.clear();
+    for fire in &session.fires {
+        state
+            .replay
+            .entry(fire.tag.clone())
+            .or_default()
+            .insert(fire.evaluation);
+    }
+    MODE.store(REPLAYING, Ordering::Relaxed);
+}
+
+/// Ends a replay; failpoints go back to their configured behavior.
+pub fn stop_replay() {
+    let mut state = state();
+    MODE.store(IDLE, Ordering::Relaxed);
+    state.replay.clear();
+}
+
+pub(crate) fn is_active() -> bool {
+    MODE.load(Ordering::Relaxed) != IDLE
+}
+
+/// Counts an evaluation of `tag`, returning its position and, while replaying, whether it
+/// has to fire.
+pub(crate) fn next_evaluation(tag: &'static str) -> (u64, Option<bool>) {
+    let mut state = state();
+    let counter = state.evaluations.entry(tag).or_insert(0);
+    *counter += 1;
+    let evaluation = *counter;
+    let replayed = (MODE.load(Ordering::Relaxed) == REPLAYING).then(|| {
+        state
+            .replay
+            .get(tag)
+            .is_some_and(|fires| fires.contains(&evaluation))
+    });
+    (evaluation, replayed)
+}
+
+pub(crate) fn record_fire(tag: &'static str, action: &str, evaluation: u64) {
+    let mut state = state();
+    if MODE.load(Ordering::Relaxed) != RECORDING {
+        return;
+    }
+    let at = state.started.elapsed();
+    state.fires.push(FireRecord {
+        tag: tag.to_string(),
+        action: action.to_string(),
+        evaluation,
+        at,
+    });
+}
@@ -0,0 +1,161 @@
+use crate::{Action, ClockSkew, Direction};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// The configuration of a single tag at the time a [`FailpointSnapshot`] was taken.
+///
+/// This covers everything set through the configuration functions. Counters and
+/// statistics that change as the tag runs (hit, poll and failure counts, sleep statistics,
+/// when and where the tag was enabled) are not captured, nor are process-wide settings
+/// such as the seed or the default action.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagState {
+    pub enabled: bool,
+    pub sleep: Option<Duration>,
+    pub chain: Option<Vec<Action>>,
+    pub escalation: Option<Vec<(Duration, Action)>>,
+    /// Whether a [`configure_per_thread`](crate::configure_per_thread) callback is set;
+    /// callbacks themselves can't be compared.
+    pub per_thread: bool,
+    pub cooldown: Option<Duration>,
+    /// Hits left and the deadline of an
+    /// [`enable_failpoint_limited`](crate::enable_failpoint_limited) budget.
+    pub limit: Option<(u64, Option<Instant>)>,
+    pub direction: Option<Direction>,
+    pub write_fraction: Option<f64>,
+    pub group: Option<&'static str>,
+    pub probability: Option<f64>,
+    pub decay: Option<(f64, Duration)>,
+    pub recovery: Option<(Duration, Duration)>,
+    pub histogram: Option<Vec<(Duration, u32)>>,
+    pub clock_skew: Option<ClockSkew>,
+    pub children: Option<Vec<&'static str>>,
+}
+
+/// A point-in-time copy of every tag's failpoint configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FailpointSnapshot {
+    tags: BTreeMap<&'static str, TagState>,
+}
+
+impl FailpointSnapshot {
+    pub fn capture() -> Self {
+        Self {
+            tags: crate::__failpoint_internal::tag_states(),
+        }
+    }
+
+    /// Returns the state of `tag`, or `None` if nothing was configured for it.
+    pub fn get(&self, tag: &str) -> Option<&TagState> {
+        self.tags.get(tag)
+    }
+
+    /// Returns the enabled tags in sorted order.
+    pub fn enabled(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.tags
+            .iter()
+            .filter(|(_, state)| state.enabled)
+            .map(|(tag, _)| *tag)
+    }
+}
+
+/// The tags whose configuration differs between a [`FailpointSnapshot`] and the current
+/// state, as returned by [`diff_config`]. Each list is sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    /// Tags configured or enabled now that had no state in the snapshot.
+    pub added: Vec<&'static str>,
+    /// Tags that had state in the snapshot and have none now.
+    pub removed: Vec<&'static str>,
+    /// Tags present in both whose [`TagState`] differs.
+    pub changed: Vec<&'static str>,
+}
+
+impl ConfigDiff {
+    /// Returns `true` if the configuration is unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares the current failpoint configuration against `snapshot`.
+///
+/// Useful for finding which test left global state behind: take a snapshot before the
+/// suspect code and diff afterwards.
+///
+/// # Example
+/// ```rust
+/// use chaos_rs::FailpointSnapshot;
+///
+/// let before = FailpointSnapshot::capture();
+/// chaos_rs::scoped("diff_doc", || {});
+/// assert!(!chaos_rs::diff_config(&before).added.contains(&"diff_doc"));
+/// ```
+pub fn diff_config(snapshot: &FailpointSnapshot) -> ConfigDiff {
+    let current = FailpointSnapshot::capture();
+    let mut diff = ConfigDiff::default();
+    for (tag, state) in &current.tags {
+        match snapshot.tags.get(tag) {
+            None => diff.added.push(tag),
+            Some(before) if before != state => diff.changed.push(tag),
+            Some(_) => {}
+        }
+    }
+    diff.removed = snapshot
+        .tags
+        .keys()
+        .filter(|tag| !current.tags.contains_key(*tag))
+        .copied()
+        .collect();
+    diff
+}
+
+/// Checks on drop that the failpoint configuration matches what it was at creation.
+///
+/// Create one at the start of a test to enforce that the test restores global state.
+/// A strict guard panics on a mismatch (unless the thread is already panicking); a
+/// lenient one only reports it, through `log` if the `log` feature is enabled and on
+/// stderr otherwise.
+///
+/// # Example
+/// ```rust
+/// let _checkpoint = chaos_rs::CheckpointGuard::strict();
+/// chaos_rs::scoped("tidy", || {});
+/// ```
+pub struct CheckpointGuard {
+    before: FailpointSnapshot,
+    strict: bool,
+}
+
+impl CheckpointGuard {
+    pub fn strict() -> Self {
+        Self {
+            before: FailpointSnapshot::capture(),
+            strict: true,
+        }
+    }
+
+    pub fn lenient() -> Self {
+        Self {
+            before: FailpointSnapshot::capture(),
+            strict: false,
+        }
+    }
+}
+
+impl Drop for CheckpointGuard {
+    fn drop(&mut self) {
+        let diff = diff_config(&self.before);
+        if diff.is_empty() {
+            return;
+        }
+        let message = format!("failpoint configuration changed during checkpoint: {diff:?}");
+        if self.strict && !std::thread::panicking() {
+            panic!("{}", message);
+        }
+        #[cfg(feature = "log")]
+        log::warn!("{}", message);
+        #[cfg(not(feature = "log"))]
+        eprintln!("{}", message);
+    }
+}
@@ -0,0 +1,188 @@
+use std::time::{Duration, Instant, SystemTime};
+
+/// Describes the window of elapsed time a timed failpoint is expected to fall into.
+///
+/// # Examples
+/// ```rust
+/// use chaos_rs::SleepExpectation;
+/// use std::time::Duration;
+///
+/// let around = SleepExpectation::around(200).tolerance(50);
+/// assert_eq!(around.min(), Duration::from_millis(150));
+/// assert_eq!(around.max(), Duration::from_millis(250));
+///
+/// let between = SleepExpectation::between(150, 260);
+/// assert!(between.contains(Duration::from_millis(255)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepExpectation {
+    min_ms: u64,
+    max_ms: u64,
+}
+
+impl SleepExpectation {
+    /// Expects exactly `ms` milliseconds; widen the window with [`tolerance`](Self::tolerance).
+    pub fn around(ms: u64) -> Self {
+        Self {
+            min_ms: ms,
+            max_ms: ms,
+        }
+    }
+
+    /// Expects between `min_ms` and `max_ms` milliseconds, inclusive.
+    pub fn between(min_ms: u64, max_ms: u64) -> Self {
+        Self {
+            min_ms: min_ms.min(max_ms),
+            max_ms: max_ms.max(min_ms),
+        }
+    }
+
+    /// Widens both ends of the window by `ms` milliseconds.
+    pub fn tolerance(self, ms: u64) -> Self {
+        Self {
+            min_ms: self.min_ms.saturating_sub(ms),
+            max_ms: self.max_ms.saturating_add(ms),
+        }
+    }
+
+    pub fn min(&self) -> Duration {
+        Duration::from_millis(self.min_ms)
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_millis(self.max_ms)
+    }
+
+    /// Scales both ends of the window by the factor set with
+    /// [`set_time_scale`](crate::set_time_scale).
+    pub fn scaled(self) -> Self {
+        let scale = crate::time_scale();
+        Self {
+            min_ms: (self.min_ms as f64 * scale) as u64,
+            max_ms: (self.max_ms as f64 * scale).ceil() as u64,
+        }
+    }
+
+    pub fn contains(&self, elapsed: Duration) -> bool {
+        elapsed >= self.min() && elapsed <= self.max()
+    }
+}
+
+/// Summary of the sleeps injected for one tag, returned by
+/// [`sleep_stats`](crate::sleep_stats).
+///
+/// # Example
+/// ```rust
+/// chaos_rs::__failpoint_internal::enable_failpoint("stats_doc");
+/// chaos_rs::maybe_sleep!("stats_doc", 5);
+/// chaos_rs::__failpoint_internal::disable_failpoint("stats_doc");
+///
+/// let stats = chaos_rs::sleep_stats("stats_doc");
+/// # #[cfg(feature = "chaos")]
+/// assert_eq!(stats.count, 1);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SleepStats {
+    /// Number of sleeps injected.
+    pub count: u64,
+    /// Shortest injected sleep, or zero if there were none.
+    pub min: Duration,
+    /// Longest injected sleep, or zero if there were none.
+    pub max: Duration,
+    /// Sum of all injected sleeps.
+    pub total: Duration,
+}
+
+impl SleepStats {
+    /// Mean injected sleep, or zero if there were none.
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => {
+                let nanos = self.total.as_nanos() / count as u128;
+                Duration::new(
+                    (nanos / 1_000_000_000) as u64,
+                    (nanos % 1_000_000_000) as u32,
+                )
+            }
+        }
+    }
+
+    pub(crate) fn record(&mut self, duration: Duration) {
+        self.min = if self.count == 0 {
+            duration
+        } else {
+            self.min.min(duration)
+        };
+        self.max = self.max.max(duration);
+        self.total += duration;
+        self.count += 1;
+    }
+}
+
+/// How far `maybe_now!` shifts the clock for a tag, set with
+/// [`configure_clock_skew`](crate::configure_clock_skew).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSkew {
+    /// The clock reads later than it really is, as after a forward jump.
+    Ahead(Duration),
+    /// The clock reads earlier than it really is, as after a backward jump.
+    Behind(Duration),
+}
+
+/// A clock reading that `maybe_now!` can skew.
+pub trait SkewClock: Sized {
+    /// Applies `skew`, returning the reading unchanged if the result isn't representable.
+    fn skewed_by(self, skew: ClockSkew) -> Self;
+}
+
+impl SkewClock for Instant {
+    fn skewed_by(self, skew: ClockSkew) -> Self {
+        match skew {
+            ClockSkew::Ahead(offset) => self.checked_add(offset),
+            ClockSkew::Behind(offset) => self.checked_sub(offset),
+        }
+        .unwrap_or(self)
+    }
+}
+
+impl SkewClock for SystemTime {
+    fn skewed_by(self, skew: ClockSkew) -> Self {
+        match skew {
+            ClockSkew::Ahead(offset) => self.checked_add(offset),
+            ClockSkew::Behind(offset) => self.checked_sub(offset),
+        }
+        .unwrap_or(self)
+    }
+}
+
+/// Returns the CPU time consumed so far by the calling thread.
+///
+/// Requires the `cpu-time` feature. On Linux this is the thread's own usage
+/// (`getrusage(RUSAGE_THREAD)`); on other Unix platforms it falls back to the whole
+/// process (`RUSAGE_SELF`). Returns `None` without the feature or on non-Unix platforms.
+pub fn thread_cpu_time() -> Option<Duration> {
+    #[cfg(all(feature = "cpu-time", unix))]
+    {
+        #[cfg(target_os = "linux")]
+        let who = libc::RUSAGE_THREAD;
+        #[cfg(not(target_os = "linux"))]
+        let who = libc::RUSAGE_SELF;
+
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+        // SAFETY: `usage` is a valid, writable `rusage` and `who` is a supported target.
+        if unsafe { libc::getrusage(who, usage.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        // SAFETY: `getrusage` succeeded, so the struct is initialized.
+        let usage = unsafe { usage.assume_init() };
+        let to_duration = |t: libc::timeval| {
+            Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+        };
+        Some(to_duration(usage.ru_utime) + to_duration(usage.ru_stime))
+    }
+    #[cfg(not(all(feature = "cpu-time", unix)))]
+    {
+        None
+    }
+}
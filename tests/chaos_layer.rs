@@ -0,0 +1,68 @@
+#![cfg(feature = "tower")]
+
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::{Action, ChaosError, ChaosLayer, clear_action, enable_failpoint_with_action};
+use std::convert::Infallible;
+use std::future::{Ready, ready};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower_layer::Layer;
+use tower_service::Service;
+
+#[derive(Clone)]
+struct Echo;
+
+impl Service<u32> for Echo {
+    type Response = u32;
+    type Error = Infallible;
+    type Future = Ready<Result<u32, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: u32) -> Self::Future {
+        ready(Ok(request))
+    }
+}
+
+#[tokio::test]
+async fn test_chaos_layer_rejects_requests() {
+    let mut service = ChaosLayer::error("layer_reject").layer(Echo);
+    assert_eq!(service.call(1).await.unwrap(), 1);
+
+    enable_failpoint("layer_reject");
+    let err = service.call(2).await.unwrap_err();
+    disable_failpoint("layer_reject");
+    let err = err.downcast_ref::<ChaosError>().unwrap();
+    assert_eq!(err.tag(), "layer_reject");
+
+    assert_eq!(service.call(3).await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_chaos_layer_adds_latency() {
+    let mut service = ChaosLayer::latency("layer_latency", Duration::from_millis(100)).layer(Echo);
+
+    enable_failpoint("layer_latency");
+    let start = Instant::now();
+    assert_eq!(service.call(4).await.unwrap(), 4);
+    disable_failpoint("layer_latency");
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn test_chaos_layer_runs_configured_action() {
+    let mut service = ChaosLayer::latency("layer_action", Duration::from_secs(5)).layer(Echo);
+
+    enable_failpoint_with_action("layer_action", Action::Error);
+    let err = service.call(5).await.unwrap_err();
+    assert!(err.downcast_ref::<ChaosError>().is_some());
+
+    enable_failpoint_with_action("layer_action", Action::Return);
+    let start = Instant::now();
+    assert_eq!(service.call(6).await.unwrap(), 6);
+    assert!(start.elapsed() < Duration::from_secs(1));
+    clear_action("layer_action");
+    disable_failpoint("layer_action");
+}
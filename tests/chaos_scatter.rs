@@ -0,0 +1,40 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::chaos_scatter;
+use std::collections::BTreeSet;
+
+#[chaos_scatter("scatter_steps")]
+fn steps(log: &mut Vec<u32>) -> Result<u32, String> {
+    log.push(1);
+    log.push(2);
+    const LAST: u32 = 3;
+    log.push(LAST);
+    Ok(log.len() as u32)
+}
+
+#[test]
+fn test_chaos_scatter_fails_at_one_random_statement() {
+    let mut log = Vec::new();
+    assert_eq!(steps(&mut log), Ok(3));
+
+    enable_failpoint("scatter_steps");
+    let mut failed_after = BTreeSet::new();
+    for seed in 0..32 {
+        chaos_rs::set_seed(seed);
+        let mut log = Vec::new();
+        assert_eq!(steps(&mut log), Err("scatter_steps".to_string()));
+        failed_after.insert(log.len());
+    }
+
+    chaos_rs::set_seed(5);
+    let mut first = Vec::new();
+    let _ = steps(&mut first);
+    chaos_rs::set_seed(5);
+    let mut second = Vec::new();
+    let _ = steps(&mut second);
+    disable_failpoint("scatter_steps");
+
+    // Four sites: before each push and before the tail expression.
+    assert!(failed_after.len() > 1, "always failed at {failed_after:?}");
+    assert!(failed_after.iter().all(|len| *len <= 3));
+    assert_eq!(first, second);
+}
@@ -0,0 +1,55 @@
+use chaos_rs::__failpoint_internal::{enable_failpoint, is_failpoint_enabled};
+use chaos_rs::chaos_test;
+
+#[chaos_test]
+fn leaky() {
+    assert!(!is_failpoint_enabled("chaos_test_before"));
+    enable_failpoint("chaos_test_leaked");
+}
+
+#[chaos_test]
+fn leaky_panic() {
+    enable_failpoint("chaos_test_leaked");
+    panic!("boom");
+}
+
+#[chaos_test]
+fn returns_value() -> Result<u32, String> {
+    chaos_rs::maybe_fail!("chaos_test_value");
+    Ok(7)
+}
+
+#[chaos_test]
+async fn leaky_async() {
+    enable_failpoint("chaos_test_leaked");
+}
+
+// Clearing is process-wide, so the scenarios run sequentially from a single test.
+#[test]
+fn test_chaos_test_isolates_failpoints() {
+    enable_failpoint("chaos_test_before");
+    leaky();
+    assert!(!is_failpoint_enabled("chaos_test_leaked"));
+
+    assert!(std::panic::catch_unwind(leaky_panic).is_err());
+    assert!(!is_failpoint_enabled("chaos_test_leaked"));
+
+    assert_eq!(returns_value(), Ok(7));
+
+    futures::executor::block_on(leaky_async());
+    assert!(!is_failpoint_enabled("chaos_test_leaked"));
+}
+
+#[chaos_test]
+#[test]
+fn test_chaos_test_composes_with_test() -> Result<(), String> {
+    // Leaks on purpose; the attribute does the teardown.
+    enable_failpoint("chaos_test_attr");
+    Ok(())
+}
+
+#[test]
+fn test_chaos_test_rejects_non_functions() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/chaos_test_not_fn.rs");
+}
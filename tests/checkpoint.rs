@@ -0,0 +1,42 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::{Action, CheckpointGuard, FailpointSnapshot};
+use std::time::Duration;
+
+#[test]
+fn test_checkpoint_guard() {
+    {
+        let _checkpoint = CheckpointGuard::strict();
+        chaos_rs::scoped("checkpoint_tidy", || {});
+    }
+
+    let result = std::panic::catch_unwind(|| {
+        let _checkpoint = CheckpointGuard::strict();
+        enable_failpoint("checkpoint_leak");
+    });
+    assert!(result.is_err());
+    assert!(
+        FailpointSnapshot::capture()
+            .enabled()
+            .eq(["checkpoint_leak"])
+    );
+
+    {
+        let _checkpoint = CheckpointGuard::lenient();
+        disable_failpoint("checkpoint_leak");
+    }
+    assert_eq!(FailpointSnapshot::capture().enabled().count(), 0);
+
+    let result = std::panic::catch_unwind(|| {
+        let _checkpoint = CheckpointGuard::strict();
+        chaos_rs::configure_decay("checkpoint_decay", 0.5, Duration::from_secs(1));
+    });
+    assert!(result.is_err());
+    chaos_rs::clear_decay("checkpoint_decay");
+
+    let result = std::panic::catch_unwind(|| {
+        let _checkpoint = CheckpointGuard::strict();
+        chaos_rs::configure_escalation("checkpoint_escalation", &[(Duration::ZERO, Action::Error)]);
+    });
+    assert!(result.is_err());
+    chaos_rs::clear_action("checkpoint_escalation");
+}
@@ -0,0 +1,20 @@
+#![cfg(all(feature = "cpu-time", unix))]
+
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_yielded_sleep_uses_little_cpu() {
+    chaos_rs::with_failpoint!("cpu_time_sleep", yielded(100, 20), {
+        chaos_rs::maybe_sleep!("cpu_time_sleep", 120);
+    });
+
+    let result = std::panic::catch_unwind(|| {
+        chaos_rs::with_failpoint!("cpu_time_spin", yielded(100, 20), {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(120) {
+                std::hint::spin_loop();
+            }
+        });
+    });
+    assert!(result.is_err(), "a busy-loop should not pass as a sleep");
+}
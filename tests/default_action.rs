@@ -0,0 +1,26 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::Action;
+use std::time::{Duration, Instant};
+
+fn call() -> Result<(), String> {
+    chaos_rs::maybe_fail!("default_action_fail");
+    Ok(())
+}
+
+#[test]
+fn test_bare_tag_uses_default_action() {
+    chaos_rs::set_default_action(Action::Sleep(Duration::from_millis(50)));
+    enable_failpoint("default_action_fail");
+
+    let start = Instant::now();
+    assert_eq!(call(), Ok(()));
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    chaos_rs::configure_chain("default_action_fail", &[Action::Error]);
+    assert!(call().is_err(), "a per-tag chain takes precedence");
+    chaos_rs::clear_chain("default_action_fail");
+
+    chaos_rs::clear_default_action();
+    assert!(call().is_err());
+    disable_failpoint("default_action_fail");
+}
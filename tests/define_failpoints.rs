@@ -0,0 +1,23 @@
+#![cfg(feature = "inventory")]
+
+use chaos_rs::{define_failpoints, known_failpoints};
+
+define_failpoints!("static_connect", "static_query");
+
+mod nested {
+    chaos_rs::define_failpoints!("static_nested");
+}
+
+#[test]
+fn test_declared_tags_are_known_before_evaluation() {
+    let known = known_failpoints();
+    for tag in ["static_connect", "static_nested", "static_query"] {
+        assert!(
+            known.contains(&tag.to_string()),
+            "{tag} missing from {known:?}"
+        );
+    }
+    assert!(!chaos_rs::__failpoint_internal::is_failpoint_enabled(
+        "static_connect"
+    ));
+}
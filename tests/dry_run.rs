@@ -0,0 +1,17 @@
+use chaos_rs::__failpoint_internal::enable_failpoint;
+
+fn do_work() -> Result<&'static str, String> {
+    chaos_rs::maybe_fail!("dry_run_fail");
+    Ok("ok")
+}
+
+#[test]
+fn test_dry_run_skips_enabled_failpoints() {
+    enable_failpoint("dry_run_fail");
+
+    chaos_rs::set_dry_run(true);
+    assert_eq!(do_work().unwrap(), "ok");
+
+    chaos_rs::set_dry_run(false);
+    assert!(do_work().is_err());
+}
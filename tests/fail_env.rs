@@ -0,0 +1,45 @@
+use chaos_rs::maybe_fail_env;
+
+fn send() -> Result<(), String> {
+    maybe_fail_env!(
+        "fail_env_test",
+        "CHAOS_RS_FAIL_ENV_TEST",
+        "send failed".to_string()
+    );
+    Ok(())
+}
+
+fn set_env(value: Option<&str>) {
+    // SAFETY: this is the only test in the binary, so no other thread reads the
+    // environment concurrently.
+    unsafe {
+        match value {
+            Some(value) => std::env::set_var("CHAOS_RS_FAIL_ENV_TEST", value),
+            None => std::env::remove_var("CHAOS_RS_FAIL_ENV_TEST"),
+        }
+    }
+}
+
+#[test]
+fn env_var_toggles_failure_at_call_time() {
+    set_env(None);
+    assert!(send().is_ok());
+    assert!(chaos_rs::known_failpoints().contains(&"fail_env_test".to_string()));
+
+    chaos_rs::__failpoint_internal::enable_failpoint("fail_env_test");
+    assert!(send().is_ok());
+
+    set_env(Some("1"));
+    assert!(send().is_err());
+
+    set_env(Some("false"));
+    assert!(send().is_ok());
+
+    set_env(Some("yes"));
+    assert!(send().is_err());
+    assert_eq!(chaos_rs::failpoint_hits("fail_env_test"), 2);
+
+    chaos_rs::__failpoint_internal::disable_failpoint("fail_env_test");
+    assert!(send().is_ok());
+    set_env(None);
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "sink")]
+
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use futures::{SinkExt, StreamExt};
+
+async fn send_all(tag: &'static str, drop_prob: f64) -> usize {
+    let (tx, rx) = futures::channel::mpsc::unbounded::<u32>();
+    let mut lossy = chaos_rs::fail_sink(tx, tag, drop_prob);
+    for item in 0..200 {
+        lossy.send(item).await.unwrap();
+    }
+    lossy.close().await.unwrap();
+    rx.count().await
+}
+
+#[tokio::test]
+async fn test_fail_sink_drops_a_fraction_of_items() {
+    chaos_rs::set_seed(11);
+
+    assert_eq!(send_all("sink_disabled", 0.5).await, 200);
+
+    enable_failpoint("sink_lossy");
+    let delivered = send_all("sink_lossy", 0.5).await;
+    let dropped = 200 - delivered;
+    disable_failpoint("sink_lossy");
+
+    assert!((60..=140).contains(&dropped), "dropped {dropped} of 200");
+    assert_eq!(chaos_rs::failpoint_hits("sink_lossy"), dropped as u64);
+}
@@ -0,0 +1,22 @@
+#![cfg(feature = "stream")]
+
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use futures::StreamExt;
+
+#[tokio::test]
+async fn test_fail_stream_injects_error_items() {
+    let source = || futures::stream::iter([Ok::<u32, String>(1), Ok(2), Ok(3)]);
+
+    let passthrough: Vec<_> = chaos_rs::fail_stream(source(), "stream_test", || "boom".into())
+        .collect()
+        .await;
+    assert_eq!(passthrough, [Ok(1), Ok(2), Ok(3)]);
+
+    enable_failpoint("stream_test");
+    let mut stream = chaos_rs::fail_stream(source(), "stream_test", || "boom".to_string());
+    assert_eq!(stream.next().await, Some(Err("boom".to_string())));
+    disable_failpoint("stream_test");
+    assert_eq!(stream.next().await, Some(Ok(2)));
+    assert_eq!(stream.next().await, Some(Ok(3)));
+    assert_eq!(stream.next().await, None);
+}
@@ -0,0 +1,31 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::{failpoint_hits, generation, known_failpoints, maybe_fail_gen, set_generation};
+
+fn replicate() -> Result<(), String> {
+    maybe_fail_gen!("gen_replicate", 2, "replica lost".to_string());
+    Ok(())
+}
+
+#[test]
+fn test_failures_target_one_generation() {
+    assert_eq!(generation(), 0);
+    assert!(replicate().is_ok());
+    assert!(known_failpoints().contains(&"gen_replicate".to_string()));
+    enable_failpoint("gen_replicate");
+
+    let outcomes: Vec<bool> = (0..4)
+        .map(|phase| {
+            set_generation(phase);
+            replicate().is_ok()
+        })
+        .collect();
+    assert_eq!(outcomes, [true, true, false, true]);
+    assert_eq!(failpoint_hits("gen_replicate"), 1);
+
+    set_generation(2);
+    assert!(replicate().is_err());
+
+    disable_failpoint("gen_replicate");
+    assert!(replicate().is_ok());
+    set_generation(0);
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "http")]
+
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::{HttpError, maybe_http_fail};
+use http::StatusCode;
+
+fn get() -> Result<&'static str, HttpError> {
+    maybe_http_fail!("http_get", StatusCode::SERVICE_UNAVAILABLE);
+    Ok("body")
+}
+
+#[test]
+fn test_maybe_http_fail_returns_synthetic_status() {
+    assert_eq!(get().unwrap(), "body");
+
+    enable_failpoint("http_get");
+    let err = get().unwrap_err();
+    disable_failpoint("http_get");
+
+    assert_eq!(err.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(err.tag(), "http_get");
+    assert_eq!(err.response().status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        err.to_string(),
+        "injected HTTP 503 Service Unavailable at 'http_get'"
+    );
+}
@@ -0,0 +1,53 @@
+use chaos_rs::__failpoint_internal::{
+    disable_failpoint, enable_failpoint, enable_failpoint_limited,
+};
+use chaos_rs::{clear_global_injection_budget, maybe_fail, set_global_injection_budget};
+use std::thread;
+use std::time::Duration;
+
+fn read() -> Result<(), String> {
+    maybe_fail!("budget_read");
+    Ok(())
+}
+
+fn write() -> Result<(), String> {
+    maybe_fail!("budget_write");
+    Ok(())
+}
+
+#[test]
+fn test_budget_caps_fires_across_threads() {
+    set_global_injection_budget(50);
+    enable_failpoint("budget_read");
+    enable_failpoint("budget_write");
+
+    let workers: Vec<_> = (0..8)
+        .map(|worker| {
+            thread::spawn(move || {
+                (0..100)
+                    .filter(|_| if worker % 2 == 0 { read() } else { write() }.is_err())
+                    .count()
+            })
+        })
+        .collect();
+    let fired: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+    assert_eq!(fired, 50);
+    assert!(read().is_ok());
+
+    clear_global_injection_budget();
+    assert!(read().is_err());
+
+    disable_failpoint("budget_read");
+    disable_failpoint("budget_write");
+
+    // Fires the budget denies leave a hit limit untouched. The budget is process-wide, so
+    // this runs in the same test.
+    set_global_injection_budget(0);
+    enable_failpoint_limited("budget_read", 2, Duration::from_secs(60));
+    assert!(read().is_ok());
+    assert!(read().is_ok());
+    clear_global_injection_budget();
+    assert!(read().is_err());
+    assert!(read().is_err());
+    assert!(read().is_ok());
+}
@@ -0,0 +1,47 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::{
+    clear_injection_log, injection_log, maybe_fail, maybe_sleep, set_injection_log_capacity,
+};
+
+fn call() -> Result<(), String> {
+    maybe_fail!("log_fail");
+    Ok(())
+}
+
+const FAIL_LINE: u32 = 7;
+
+#[test]
+fn test_injection_log_records_tag_action_and_location() {
+    clear_injection_log();
+    assert!(call().is_ok());
+    assert!(injection_log().is_empty());
+
+    enable_failpoint("log_fail");
+    enable_failpoint("log_sleep");
+    let _ = call();
+    maybe_sleep!("log_sleep", 1);
+    let sleep_line = line!() - 1;
+    disable_failpoint("log_fail");
+    disable_failpoint("log_sleep");
+
+    let log = injection_log();
+    assert_eq!(log.len(), 2);
+    assert_eq!((log[0].tag, log[0].action), ("log_fail", "fail"));
+    assert_eq!(log[0].location.file(), file!());
+    assert_eq!(log[0].location.line(), FAIL_LINE);
+    assert_eq!((log[1].tag, log[1].action), ("log_sleep", "sleep"));
+    assert_eq!(log[1].location.line(), sleep_line);
+
+    set_injection_log_capacity(3);
+    enable_failpoint("log_fail");
+    for _ in 0..5 {
+        let _ = call();
+    }
+    disable_failpoint("log_fail");
+    let log = injection_log();
+    assert_eq!(log.len(), 3);
+    assert!(log.iter().all(|record| record.tag == "log_fail"));
+
+    set_injection_log_capacity(1024);
+    clear_injection_log();
+}
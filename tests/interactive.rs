@@ -0,0 +1,34 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::{maybe_fail, set_interactive, step};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+fn call() -> Result<(), String> {
+    maybe_fail!("interactive_fail");
+    Ok(())
+}
+
+#[test]
+fn test_interactive_mode_blocks_until_step() {
+    set_interactive(true);
+    enable_failpoint("interactive_fail");
+
+    let (tx, rx) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        for _ in 0..2 {
+            tx.send(call()).unwrap();
+        }
+    });
+
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    step();
+    assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap().is_err());
+
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    set_interactive(false);
+    assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap().is_err());
+    worker.join().unwrap();
+
+    disable_failpoint("interactive_fail");
+}
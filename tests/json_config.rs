@@ -0,0 +1,71 @@
+#![cfg(feature = "json")]
+
+use chaos_rs::__failpoint_internal::{disable_failpoint, is_failpoint_enabled};
+use chaos_rs::{ConfigAction, FailpointConfig, FailpointSnapshot};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+fn fetch() -> Result<(), String> {
+    chaos_rs::maybe_fail!("json_error");
+    Ok(())
+}
+
+#[test]
+fn test_configure_json_round_trip() {
+    let mut configs = BTreeMap::new();
+    configs.insert(
+        "json_error".to_string(),
+        FailpointConfig {
+            action: ConfigAction::Error,
+            ms: None,
+            prob: None,
+        },
+    );
+    configs.insert(
+        "json_sleep".to_string(),
+        FailpointConfig {
+            action: ConfigAction::Sleep,
+            ms: Some(200),
+            prob: Some(0.5),
+        },
+    );
+
+    let json = serde_json::to_string(&configs).unwrap();
+    assert_eq!(
+        json,
+        r#"{"json_error":{"action":"error"},"json_sleep":{"action":"sleep","ms":200,"prob":0.5}}"#
+    );
+    let parsed: BTreeMap<String, FailpointConfig> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, configs);
+
+    chaos_rs::configure_json(&json).unwrap();
+    assert!(fetch().is_err());
+
+    let snapshot = FailpointSnapshot::capture();
+    let sleep = snapshot.get("json_sleep").unwrap();
+    assert!(sleep.enabled);
+    assert_eq!(
+        sleep.chain,
+        Some(vec![chaos_rs::Action::Sleep(Duration::from_millis(200))])
+    );
+    assert_eq!(sleep.probability, Some(0.5));
+
+    disable_failpoint("json_error");
+    disable_failpoint("json_sleep");
+}
+
+#[test]
+fn test_configure_json_rejects_malformed_input() {
+    let err = chaos_rs::configure_json(r#"{"json_bad": {"action": "explode"}"#).unwrap_err();
+    assert!(err.to_string().starts_with("invalid failpoint JSON"));
+
+    let err = chaos_rs::configure_json(
+        r#"{"json_ok": {"action": "error"}, "json_no_ms": {"action": "sleep"}}"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "failpoint 'json_no_ms': sleep action requires \"ms\""
+    );
+    assert!(!is_failpoint_enabled("json_ok"));
+}
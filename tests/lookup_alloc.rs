@@ -0,0 +1,69 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint, is_failpoint_enabled};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        LIVE_BYTES.with(|live| live.set(live.get() + layout.size() as isize));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.with(|live| live.set(live.get() - layout.size() as isize));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+fn call() -> Result<(), String> {
+    chaos_rs::maybe_fail!("alloc_lookup_disabled");
+    Ok(())
+}
+
+#[test]
+fn test_lookup_does_not_allocate() {
+    enable_failpoint("alloc_lookup_enabled");
+    let runtime_tag = String::from("alloc_lookup_enabled");
+    // The first evaluation initializes the lazily created stores.
+    let _ = call();
+
+    let allocations = allocations_during(|| {
+        assert!(is_failpoint_enabled(&runtime_tag));
+        assert!(!is_failpoint_enabled("alloc_lookup_disabled"));
+        assert_eq!(call(), Ok(()));
+    });
+    disable_failpoint("alloc_lookup_enabled");
+
+    assert_eq!(allocations, 0);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_reapplying_json_config_does_not_leak_tags() {
+    let json =
+        r#"{"alloc_json_a": {"action": "error"}, "alloc_json_b": {"action": "sleep", "ms": 5}}"#;
+    chaos_rs::configure_json(json).unwrap();
+
+    let before = LIVE_BYTES.with(Cell::get);
+    chaos_rs::configure_json(json).unwrap();
+    let retained = LIVE_BYTES.with(Cell::get) - before;
+    chaos_rs::clear_all_failpoints();
+
+    assert_eq!(retained, 0);
+}
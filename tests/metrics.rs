@@ -0,0 +1,39 @@
+#![cfg(feature = "metrics")]
+
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+
+fn query() -> Result<(), String> {
+    chaos_rs::maybe_fail!("db_error");
+    Ok(())
+}
+
+fn fetch() -> Result<(), String> {
+    chaos_rs::maybe_fail!("cache \"miss\"");
+    Ok(())
+}
+
+#[test]
+fn test_encode_metrics_format() {
+    enable_failpoint("db_error");
+    for _ in 0..5 {
+        let _ = query();
+    }
+    enable_failpoint("cache \"miss\"");
+    let _ = fetch();
+    disable_failpoint("cache \"miss\"");
+
+    assert_eq!(
+        chaos_rs::encode_metrics(),
+        "# HELP chaos_failpoint_enabled Whether the failpoint is enabled.\n\
+         # TYPE chaos_failpoint_enabled gauge\n\
+         chaos_failpoint_enabled{tag=\"db_error\"} 1\n\
+         # HELP chaos_failpoint_hits How many times the failpoint has fired.\n\
+         # TYPE chaos_failpoint_hits counter\n\
+         chaos_failpoint_hits{tag=\"cache \\\"miss\\\"\"} 1\n\
+         chaos_failpoint_hits{tag=\"db_error\"} 5\n"
+    );
+    disable_failpoint("db_error");
+
+    chaos_rs::reset_failpoint_count("db_error");
+    assert!(chaos_rs::encode_metrics().contains("chaos_failpoint_hits{tag=\"db_error\"} 5\n"));
+}
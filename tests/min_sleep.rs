@@ -0,0 +1,22 @@
+use chaos_rs::{maybe_sleep, min_sleep, set_min_sleep, sleep_stats, with_failpoint};
+use std::time::Duration;
+
+#[test]
+fn min_sleep_raises_small_sleeps() {
+    set_min_sleep(Duration::from_millis(80));
+    assert_eq!(min_sleep(), Duration::from_millis(80));
+
+    with_failpoint!("min_sleep_floor", at_least(80), {
+        maybe_sleep!("min_sleep_floor", 5);
+    });
+    assert_eq!(
+        sleep_stats("min_sleep_floor").max,
+        Duration::from_millis(80)
+    );
+
+    with_failpoint!("min_sleep_longer", at_least(120), {
+        maybe_sleep!("min_sleep_longer", 120);
+    });
+
+    set_min_sleep(Duration::ZERO);
+}
@@ -0,0 +1,63 @@
+use chaos_rs::__failpoint_internal::enable_failpoint;
+use chaos_rs::{activity_report, enabled_failpoints, known_failpoints, maybe_fail};
+use std::thread;
+
+const TAGS: [&str; 8] = [
+    "order_h", "order_c", "order_f", "order_a", "order_g", "order_b", "order_e", "order_d",
+];
+
+fn probe(index: usize) -> Result<(), String> {
+    match index {
+        0 => {
+            maybe_fail!("order_h");
+        }
+        1 => {
+            maybe_fail!("order_c");
+        }
+        2 => {
+            maybe_fail!("order_f");
+        }
+        3 => {
+            maybe_fail!("order_a");
+        }
+        4 => {
+            maybe_fail!("order_g");
+        }
+        5 => {
+            maybe_fail!("order_b");
+        }
+        6 => {
+            maybe_fail!("order_e");
+        }
+        _ => {
+            maybe_fail!("order_d");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_listings_are_sorted_under_concurrency() {
+    let workers: Vec<_> = (0..TAGS.len())
+        .map(|index| {
+            thread::spawn(move || {
+                enable_failpoint(TAGS[index]);
+                let _ = probe(index);
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    let mut sorted = TAGS.to_vec();
+    sorted.sort();
+    assert_eq!(enabled_failpoints(), sorted);
+    assert_eq!(known_failpoints(), sorted);
+
+    let expected: String = sorted
+        .iter()
+        .map(|tag| format!("{tag}: enabled, 1 hits\n"))
+        .collect();
+    assert_eq!(activity_report(), expected);
+}
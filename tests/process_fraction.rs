@@ -0,0 +1,27 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, is_failpoint_enabled};
+use chaos_rs::{enable_failpoint_on_fraction, set_process_seed};
+
+#[test]
+fn test_enable_failpoint_on_fraction() {
+    set_process_seed(1234);
+    assert!(!enable_failpoint_on_fraction("fraction_tag", 0.0));
+    assert!(!is_failpoint_enabled("fraction_tag"));
+    assert!(enable_failpoint_on_fraction("fraction_tag", 1.0));
+    assert!(is_failpoint_enabled("fraction_tag"));
+    disable_failpoint("fraction_tag");
+
+    let first = enable_failpoint_on_fraction("fraction_tag", 0.5);
+    for _ in 0..10 {
+        assert_eq!(enable_failpoint_on_fraction("fraction_tag", 0.5), first);
+    }
+    disable_failpoint("fraction_tag");
+
+    let participating = (0..1000)
+        .filter(|seed| {
+            set_process_seed(*seed);
+            enable_failpoint_on_fraction("fraction_tag", 0.3)
+        })
+        .count();
+    assert!((200..400).contains(&participating));
+    disable_failpoint("fraction_tag");
+}
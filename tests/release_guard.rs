@@ -0,0 +1,9 @@
+// trybuild builds with the dev profile, so debug assertions are turned off to make the
+// guard see a release-like build. The `chaos` feature comes from the dev-dependency on
+// this crate.
+#[test]
+fn test_chaos_rejected_in_release() {
+    unsafe { std::env::set_var("CARGO_PROFILE_DEV_DEBUG_ASSERTIONS", "false") };
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/chaos_in_release.rs");
+}
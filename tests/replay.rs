@@ -0,0 +1,38 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+
+fn call() -> Result<(), String> {
+    chaos_rs::maybe_fail!("replay_flaky");
+    Ok(())
+}
+
+fn run() -> Vec<bool> {
+    (0..32).map(|_| call().is_err()).collect()
+}
+
+#[test]
+fn test_replay_reproduces_recorded_fires() {
+    chaos_rs::set_seed(3);
+    chaos_rs::configure_probability("replay_flaky", 0.5);
+    enable_failpoint("replay_flaky");
+
+    chaos_rs::start_recording();
+    let recorded = run();
+    let session = chaos_rs::stop_recording();
+
+    assert!(recorded.contains(&true) && recorded.contains(&false));
+    assert_eq!(
+        session.fires.len(),
+        recorded.iter().filter(|failed| **failed).count()
+    );
+    assert!(session.fires.iter().all(|fire| fire.tag == "replay_flaky"));
+
+    // Different randomness and a disabled tag don't matter while replaying.
+    disable_failpoint("replay_flaky");
+    chaos_rs::set_seed(99);
+    chaos_rs::replay(&session);
+    let replayed = run();
+    chaos_rs::stop_replay();
+
+    assert_eq!(replayed, recorded);
+    assert!(call().is_ok());
+}
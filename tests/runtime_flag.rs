@@ -0,0 +1,45 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use std::time::{Duration, Instant};
+
+fn fetch() -> Result<(), String> {
+    chaos_rs::maybe_fail_runtime!("runtime_flag_fail");
+    Ok(())
+}
+
+fn gated() -> Result<(), String> {
+    chaos_rs::maybe_fail!("runtime_flag_gated");
+    Ok(())
+}
+
+fn slow() {
+    chaos_rs::maybe_sleep_runtime!("runtime_flag_sleep", 30);
+}
+
+#[test]
+fn test_runtime_macros_follow_master_switch() {
+    enable_failpoint("runtime_flag_fail");
+    enable_failpoint("runtime_flag_sleep");
+    enable_failpoint("runtime_flag_gated");
+
+    assert!(fetch().is_ok(), "the switch is off by default");
+    assert!(
+        gated().is_err(),
+        "the feature-gated macros ignore the switch"
+    );
+
+    chaos_rs::set_runtime_chaos(true);
+    assert!(fetch().is_err());
+    let start = Instant::now();
+    slow();
+    assert!(start.elapsed() >= Duration::from_millis(30));
+    assert!(
+        std::panic::catch_unwind(|| chaos_rs::maybe_panic_runtime!("runtime_flag_fail")).is_err()
+    );
+
+    chaos_rs::set_runtime_chaos(false);
+    assert!(fetch().is_ok());
+
+    disable_failpoint("runtime_flag_fail");
+    disable_failpoint("runtime_flag_sleep");
+    disable_failpoint("runtime_flag_gated");
+}
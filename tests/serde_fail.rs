@@ -0,0 +1,51 @@
+#![cfg(feature = "serde")]
+
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::maybe_serde_fail;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, PartialEq)]
+struct Reading(f64);
+
+impl Serialize for Reading {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        maybe_serde_fail!("serde_encode");
+        serializer.serialize_f64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Reading {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        maybe_serde_fail!("serde_decode", deserialize);
+        f64::deserialize(deserializer).map(Reading)
+    }
+}
+
+#[test]
+fn test_maybe_serde_fail_in_serialize() {
+    assert_eq!(serde_json::to_string(&Reading(1.5)).unwrap(), "1.5");
+
+    enable_failpoint("serde_encode");
+    let err = serde_json::to_string(&Reading(1.5)).unwrap_err();
+    disable_failpoint("serde_encode");
+    assert_eq!(
+        err.to_string(),
+        "injected serialization failure at 'serde_encode'"
+    );
+}
+
+#[test]
+fn test_maybe_serde_fail_in_deserialize() {
+    assert_eq!(
+        serde_json::from_str::<Reading>("2.5").unwrap(),
+        Reading(2.5)
+    );
+
+    enable_failpoint("serde_decode");
+    let err = serde_json::from_str::<Reading>("2.5").unwrap_err();
+    disable_failpoint("serde_decode");
+    assert!(
+        err.to_string()
+            .starts_with("injected deserialization failure at 'serde_decode'")
+    );
+}
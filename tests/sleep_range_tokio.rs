@@ -0,0 +1,34 @@
+#![cfg(feature = "tokio")]
+
+use chaos_rs::__failpoint_internal::enable_failpoint;
+use std::time::Duration;
+
+async fn jittery() {
+    chaos_rs::maybe_sleep_range_async!("tokio_sleep_range", 100, 500);
+}
+
+async fn measure() -> Duration {
+    let start = tokio::time::Instant::now();
+    jittery().await;
+    start.elapsed()
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_sleep_range_async_is_deterministic_with_paused_time() {
+    enable_failpoint("tokio_sleep_range");
+
+    chaos_rs::set_seed(7);
+    let first = measure().await;
+    chaos_rs::set_seed(7);
+    let second = measure().await;
+
+    assert_eq!(first, second);
+    assert!(first >= Duration::from_millis(100) && first <= Duration::from_millis(501));
+
+    // A configured action runs instead of the sleep without drawing a duration.
+    chaos_rs::set_seed(7);
+    chaos_rs::configure_chain("tokio_sleep_range", &[chaos_rs::Action::Return]);
+    assert_eq!(measure().await, Duration::ZERO);
+    chaos_rs::clear_action("tokio_sleep_range");
+    assert_eq!(measure().await, first);
+}
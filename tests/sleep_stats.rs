@@ -0,0 +1,30 @@
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use std::time::Duration;
+
+fn jittery() {
+    chaos_rs::maybe_sleep_range!("sleep_stats_range", 1, 20);
+}
+
+#[test]
+fn test_sleep_stats_track_injected_sleeps() {
+    enable_failpoint("sleep_stats_range");
+    for _ in 0..10 {
+        jittery();
+    }
+    disable_failpoint("sleep_stats_range");
+
+    let stats = chaos_rs::sleep_stats("sleep_stats_range");
+    assert_eq!(stats.count, 10);
+    assert!(stats.min >= Duration::from_millis(1));
+    assert!(stats.max <= Duration::from_millis(20));
+    assert!(stats.min <= stats.mean() && stats.mean() <= stats.max);
+
+    jittery();
+    assert_eq!(chaos_rs::sleep_stats("sleep_stats_range").count, 10);
+
+    chaos_rs::clear_all_failpoints();
+    assert_eq!(
+        chaos_rs::sleep_stats("sleep_stats_range"),
+        chaos_rs::SleepStats::default()
+    );
+}
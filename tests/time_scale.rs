@@ -0,0 +1,27 @@
+use chaos_rs::{maybe_sleep, set_time_scale, time_scale, with_failpoint};
+use std::time::{Duration, Instant};
+
+#[test]
+fn time_scale_halves_sleeps_and_bounds() {
+    set_time_scale(0.5);
+    assert_eq!(time_scale(), 0.5);
+
+    let start = Instant::now();
+    with_failpoint!("time_scale_sleep", 200, 40, {
+        maybe_sleep!("time_scale_sleep", 200);
+    });
+    assert!(start.elapsed() < Duration::from_millis(150));
+
+    with_failpoint!("time_scale_total", total_sleep(200, 10), {
+        maybe_sleep!("time_scale_total", 100);
+        maybe_sleep!("time_scale_total", 100);
+    });
+
+    set_time_scale(1.0);
+}
+
+#[test]
+#[should_panic(expected = "time scale must be finite and non-negative")]
+fn time_scale_rejects_infinity() {
+    set_time_scale(f64::INFINITY);
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "tokio")]
+
+use chaos_rs::__failpoint_internal::{disable_failpoint, enable_failpoint};
+use chaos_rs::{maybe_fail_recv_async, maybe_fail_send_async};
+use tokio::sync::mpsc::error::SendError;
+
+#[tokio::test]
+async fn test_maybe_fail_send_and_recv_async() {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<u32>(4);
+
+    let sent: Result<(), SendError<u32>> =
+        maybe_fail_send_async!("tokio_send", tx, 1, SendError(1));
+    sent.unwrap();
+
+    enable_failpoint("tokio_send");
+    let sent: Result<(), SendError<u32>> =
+        maybe_fail_send_async!("tokio_send", tx, 2, SendError(2));
+    disable_failpoint("tokio_send");
+    assert_eq!(sent, Err(SendError(2)));
+
+    enable_failpoint("tokio_recv");
+    let received: Result<Option<u32>, String> =
+        maybe_fail_recv_async!("tokio_recv", rx, "recv failed".to_string());
+    disable_failpoint("tokio_recv");
+    assert_eq!(received, Err("recv failed".to_string()));
+
+    let received: Result<Option<u32>, String> =
+        maybe_fail_recv_async!("tokio_recv", rx, "recv failed".to_string());
+    assert_eq!(received, Ok(Some(1)));
+
+    drop(tx);
+    let received: Result<Option<u32>, String> =
+        maybe_fail_recv_async!("tokio_recv", rx, "recv failed".to_string());
+    assert_eq!(received, Ok(None));
+}
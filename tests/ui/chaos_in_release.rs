@@ -0,0 +1,3 @@
+chaos_rs::assert_chaos_disabled_in_release!();
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[chaos_rs::chaos_test]
+struct NotATest;
+
+fn main() {}